@@ -131,3 +131,26 @@ pub fn bnb(instance: &Instance, mode: SearchMode) -> Solution {
         value: best_node.value,
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use held_karp::{Objective, cost, held_karp};
+
+    // Without an initial upperbound ulysses16 and gr17 take more than a minute
+    // even in release builds, so only burma14 is checked
+    #[test]
+    fn bnb_matches_held_karp() {
+        let instance = instance_reader::read_data("instances/burma14.tsp");
+        let (_, optimum) = held_karp(&instance, Objective::Tour).unwrap();
+
+        for mode in [SearchMode::Bfs, SearchMode::Dfs] {
+            let s = bnb(&instance, mode);
+            let mut nodes = s.sequence[..instance.dimension].to_vec();
+            nodes.sort_unstable();
+            assert!(nodes.into_iter().eq(0..instance.dimension));
+            assert_eq!(cost(&instance, Objective::Tour, &s.sequence), s.value);
+            assert_eq!(s.value, optimum);
+        }
+    }
+}
@@ -0,0 +1,243 @@
+use rand::Rng;
+use std::str::FromStr;
+use std::sync::{Condvar, Mutex};
+
+// Sharpens the reactive probabilities towards the alphas with the better averages
+const REACTIVE_EXPONENT: f64 = 10.0;
+
+// The reactive probabilities are updated once every this many starts
+const ROUND_SIZE: usize = 5;
+
+// Fraction of the sorted candidates that the GRASP chooses from at each step
+#[derive(Debug, Default, Clone, PartialEq)]
+pub enum Alpha {
+    // Drawn uniformly at every step
+    #[default]
+    Random,
+    Fixed(f64),
+    // One value of the set for each start, uniformly
+    Set(Vec<f64>),
+    // One value of the set for each start, with probabilities proportional to
+    // (best / average)^REACTIVE_EXPONENT, where average is the mean value of the solutions
+    // found from the starts that used it and best is the smallest of those averages
+    Reactive(Vec<f64>),
+}
+
+// random, a single value, or set:A,B,... and reactive:A,B,... with values in [0, 1]
+impl FromStr for Alpha {
+    type Err = ();
+
+    fn from_str(value: &str) -> Result<Self, ()> {
+        if value == "random" {
+            return Ok(Alpha::Random);
+        }
+
+        let parse = |alpha: &str| {
+            alpha
+                .parse()
+                .ok()
+                .filter(|alpha| (0.0..=1.0).contains(alpha))
+                .ok_or(())
+        };
+        if let Some(list) = value.strip_prefix("set:") {
+            return list
+                .split(',')
+                .map(parse)
+                .collect::<Result<_, _>>()
+                .map(Alpha::Set);
+        }
+        if let Some(list) = value.strip_prefix("reactive:") {
+            return list
+                .split(',')
+                .map(parse)
+                .collect::<Result<_, _>>()
+                .map(Alpha::Reactive);
+        }
+
+        parse(value).map(Alpha::Fixed)
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Start {
+    Pending,
+    // Taken after the search stopped, it never runs
+    Skipped,
+    // Index of the alpha and value found
+    Done(usize, u32),
+}
+
+// Alpha of every start, shared by the threads
+// The reactive probabilities of a start only depend on the starts of the previous rounds,
+// so the alphas are the same with any number of threads. A start waits for the starts
+// of the previous rounds to end, they were all taken before it so they can't wait on it
+pub struct GraspAlpha {
+    alpha: Alpha,
+    starts: Mutex<Vec<Start>>,
+    reported: Condvar,
+}
+
+impl GraspAlpha {
+    pub fn new(alpha: &Alpha, starts: usize) -> Self {
+        GraspAlpha {
+            alpha: alpha.clone(),
+            starts: Mutex::new(vec![Start::Pending; starts]),
+            reported: Condvar::new(),
+        }
+    }
+
+    // Alpha for a start and its index in the set, None draws alpha at every step
+    pub fn choose(&self, start: usize, rng: &mut impl Rng) -> (Option<f64>, usize) {
+        match &self.alpha {
+            Alpha::Random => (None, 0),
+            Alpha::Fixed(alpha) => (Some(*alpha), 0),
+            Alpha::Set(values) => {
+                let index = rng.random_range(0..values.len());
+                (Some(values[index]), index)
+            }
+            Alpha::Reactive(values) => {
+                let weights = self.reactive_weights(start, values.len());
+                let mut left = rng.random::<f64>() * weights.iter().sum::<f64>();
+                let index = weights
+                    .iter()
+                    .position(|&weight| {
+                        left -= weight;
+                        left < 0.0
+                    })
+                    .unwrap_or(values.len() - 1);
+                (Some(values[index]), index)
+            }
+        }
+    }
+
+    // Value of the best solution found from a start with the alpha at index
+    pub fn report(&self, start: usize, index: usize, value: u32) {
+        self.starts.lock().unwrap()[start] = Start::Done(index, value);
+        self.reported.notify_all();
+    }
+
+    // Start taken once the search stopped, the starts after it don't wait for it
+    pub fn skip(&self, start: usize) {
+        self.starts.lock().unwrap()[start] = Start::Skipped;
+        self.reported.notify_all();
+    }
+
+    // Weights from the starts of the previous rounds, in start order
+    // Alphas that weren't used yet weigh as much as the best one
+    fn reactive_weights(&self, start: usize, size: usize) -> Vec<f64> {
+        let previous = start / ROUND_SIZE * ROUND_SIZE;
+        let starts = self
+            .reported
+            .wait_while(self.starts.lock().unwrap(), |starts| {
+                starts[..previous]
+                    .iter()
+                    .any(|start| matches!(start, Start::Pending))
+            })
+            .unwrap();
+
+        let mut history = vec![(0.0, 0); size];
+        for start in &starts[..previous] {
+            if let Start::Done(index, value) = *start {
+                history[index].0 += value as f64;
+                history[index].1 += 1;
+            }
+        }
+
+        let averages: Vec<Option<f64>> = history
+            .iter()
+            .map(|&(sum, count)| (count > 0).then(|| sum / count as f64))
+            .collect();
+        let best = averages.iter().flatten().copied().fold(f64::MAX, f64::min);
+
+        averages
+            .iter()
+            .map(|average| match average {
+                Some(average) if *average > 0.0 => (best / average).powf(REACTIVE_EXPONENT),
+                _ => 1.0,
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::SeedableRng;
+    use rand::rngs::StdRng;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::thread;
+
+    #[test]
+    fn parses_alpha() {
+        assert_eq!("random".parse(), Ok(Alpha::Random));
+        assert_eq!("0.25".parse(), Ok(Alpha::Fixed(0.25)));
+        assert_eq!("set:0,1".parse(), Ok(Alpha::Set(vec![0.0, 1.0])));
+        assert_eq!(
+            "reactive:0.1,0.2".parse(),
+            Ok(Alpha::Reactive(vec![0.1, 0.2]))
+        );
+        for invalid in ["1.5", "-0.1", "set:", "reactive:0.1,", "set:0.1,2", "fixed"] {
+            assert_eq!(invalid.parse::<Alpha>(), Err(()), "{invalid}");
+        }
+    }
+
+    // Alpha index chosen by every start, the starts are spread over the threads as in the ILS
+    // and end in an order that depends on the thread timing
+    fn reactive_choices(threads: usize) -> Vec<usize> {
+        let starts = 40;
+        let grasp_alpha = GraspAlpha::new(&Alpha::Reactive(vec![0.0, 0.1, 0.2, 0.3]), starts);
+        let next_start = AtomicUsize::new(0);
+        let chosen: Vec<AtomicUsize> = (0..starts).map(|_| AtomicUsize::new(0)).collect();
+
+        thread::scope(|scope| {
+            for _ in 0..threads {
+                scope.spawn(|| {
+                    loop {
+                        let start = next_start.fetch_add(1, Ordering::Relaxed);
+                        if start >= starts {
+                            return;
+                        }
+
+                        let mut rng = StdRng::seed_from_u64(start as u64);
+                        let (_, index) = grasp_alpha.choose(start, &mut rng);
+                        thread::sleep(std::time::Duration::from_micros(
+                            (start * 7919 % 13) as u64 * 100,
+                        ));
+                        // The smaller alphas find better solutions
+                        grasp_alpha.report(start, index, 1000 + 100 * index as u32);
+                        chosen[start].store(index, Ordering::Relaxed);
+                    }
+                });
+            }
+        });
+
+        chosen.into_iter().map(AtomicUsize::into_inner).collect()
+    }
+
+    #[test]
+    fn reactive_choices_do_not_depend_on_threads() {
+        let sequential = reactive_choices(1);
+        for threads in [2, 4, 7] {
+            assert_eq!(reactive_choices(threads), sequential, "{threads} threads");
+        }
+
+        // The best alpha is favoured once there are results
+        let late = &sequential[20..];
+        assert!(late.iter().filter(|&&index| index == 0).count() > late.len() / 2);
+    }
+
+    #[test]
+    fn skipped_starts_do_not_block() {
+        let grasp_alpha = GraspAlpha::new(&Alpha::Reactive(vec![0.0, 0.5]), 2 * ROUND_SIZE);
+        let mut rng = StdRng::seed_from_u64(0);
+        for start in 0..ROUND_SIZE {
+            if start == 1 {
+                grasp_alpha.skip(start);
+            } else {
+                grasp_alpha.report(start, 0, 10);
+            }
+        }
+        // Returns instead of waiting for the skipped start
+        assert!(grasp_alpha.choose(ROUND_SIZE, &mut rng).0.is_some());
+    }
+}
@@ -0,0 +1,150 @@
+use instance_reader::Instance;
+
+// The tables take 5 bytes for each subset of nodes and last node, 2^(n-1) * (n-1) states
+const MEMORY_LIMIT: usize = 1 << 30;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Objective {
+    // Length of the tour
+    Tour,
+    // Sum of the arrival times at every node, the return to the depot included
+    Latency,
+}
+
+impl Objective {
+    // Weight of the edge that leaves a path through visited customers
+    // The edge that leaves a set of k customers delays the n - k arrivals after it,
+    // so the latency is a sum of edge costs weighted by the size of the set
+    fn weight(self, dimension: usize, visited: usize) -> u32 {
+        match self {
+            Objective::Tour => 1,
+            Objective::Latency => (dimension - visited) as u32,
+        }
+    }
+}
+
+// Largest dimension that fits in the memory limit
+pub fn max_dimension() -> usize {
+    (2..usize::BITS as usize)
+        .take_while(|&n| (1usize << (n - 1)) * (n - 1) * 5 <= MEMORY_LIMIT)
+        .last()
+        .unwrap()
+}
+
+// Cost of a sequence that starts and ends at node 0
+pub fn cost(instance: &Instance, objective: Objective, sequence: &[usize]) -> u32 {
+    sequence
+        .windows(2)
+        .enumerate()
+        .map(|(visited, edge)| {
+            objective.weight(instance.dimension, visited) * instance.distance(edge[0], edge[1])
+        })
+        .sum()
+}
+
+// Held-Karp dynamic programming over subsets of visited nodes, O(2^n * n²)
+// cost[set][last] is the cheapest path from node 0 through every node in set ending at last
+// Node 0 is left out of the sets, so bit i stands for node i + 1
+// Returns the optimal sequence and its cost, or None when the instance doesn't fit in the memory limit
+pub fn held_karp(instance: &Instance, objective: Objective) -> Option<(Vec<usize>, u32)> {
+    let n = instance.dimension;
+    if n > max_dimension() {
+        return None;
+    }
+    if n < 2 {
+        return Some((vec![0, 0], 0));
+    }
+
+    let m = n - 1;
+    let sets = 1usize << m;
+    let mut cost = vec![u32::MAX; sets * m];
+    let mut parent = vec![u8::MAX; sets * m];
+
+    for last in 0..m {
+        cost[(1 << last) * m + last] = objective.weight(n, 0) * instance.distance(0, last + 1);
+    }
+
+    for set in 1..sets {
+        let weight = objective.weight(n, set.count_ones() as usize);
+        for last in (0..m).filter(|&last| set & (1 << last) != 0) {
+            let current = cost[set * m + last];
+            if current == u32::MAX {
+                continue;
+            }
+
+            for next in (0..m).filter(|&next| set & (1 << next) == 0) {
+                let next_set = set | (1 << next);
+                let value = current + weight * instance.distance(last + 1, next + 1);
+                if value < cost[next_set * m + next] {
+                    cost[next_set * m + next] = value;
+                    parent[next_set * m + next] = last as u8;
+                }
+            }
+        }
+    }
+
+    // Return to the depot
+    let full = sets - 1;
+    let weight = objective.weight(n, m);
+    let (value, mut last) = (0..m)
+        .map(|last| {
+            let value = cost[full * m + last] + weight * instance.distance(last + 1, 0);
+            (value, last)
+        })
+        .min()
+        .unwrap();
+
+    let mut sequence = vec![0];
+    let mut set = full;
+    while set != 0 {
+        sequence.push(last + 1);
+        let previous = parent[set * m + last];
+        set &= !(1 << last);
+        last = previous as usize;
+    }
+    sequence.push(0);
+    // The path was rebuilt from the end
+    sequence.reverse();
+
+    Some((sequence, value))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Optimal tour lengths published with TSPLIB
+    #[test]
+    fn tour_matches_known_optima() {
+        for (name, optimum) in [("burma14", 3323), ("gr17", 2085)] {
+            let instance = instance_reader::read_data(&format!("instances/{name}.tsp"));
+            let (sequence, value) = held_karp(&instance, Objective::Tour).unwrap();
+            assert_eq!(value, optimum, "{name}");
+            assert_eq!(cost(&instance, Objective::Tour, &sequence), value, "{name}");
+        }
+    }
+
+    #[test]
+    fn latency_matches_sequence_cost() {
+        for name in ["burma14", "ulysses16", "gr17"] {
+            let instance = instance_reader::read_data(&format!("instances/{name}.tsp"));
+            let (sequence, value) = held_karp(&instance, Objective::Latency).unwrap();
+
+            let mut visited = sequence[..instance.dimension].to_vec();
+            visited.sort_unstable();
+            assert!(visited.into_iter().eq(0..instance.dimension), "{name}");
+            assert_eq!(
+                cost(&instance, Objective::Latency, &sequence),
+                value,
+                "{name}"
+            );
+
+            // The reversed sequence has a different latency, but never a smaller one
+            let reversed: Vec<usize> = sequence.iter().rev().copied().collect();
+            assert!(
+                cost(&instance, Objective::Latency, &reversed) >= value,
+                "{name}"
+            );
+        }
+    }
+}
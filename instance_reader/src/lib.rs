@@ -5,6 +5,7 @@ pub struct Instance {
     pub dimension: usize,
     pub name: String,
     matrix: Vec<u32>,
+    coords: Option<Vec<(f32, f32)>>,
 }
 
 impl Instance {
@@ -16,6 +17,74 @@ impl Instance {
     pub fn matrix_slice(&self) -> &[u32] {
         &self.matrix
     }
+
+    // Only available when the distances were computed from coordinates
+    pub fn coords(&self) -> Option<&[(f32, f32)]> {
+        self.coords.as_deref()
+    }
+
+    // The k closest nodes to each node, sorted by distance
+    pub fn nearest_neighbours(&self, k: usize) -> Vec<Vec<usize>> {
+        let n = self.dimension;
+        let k = k.min(n.saturating_sub(1));
+
+        (0..n)
+            .map(|i| {
+                let mut others: Vec<usize> = (0..n).filter(|&j| j != i).collect();
+                if k < others.len() {
+                    others.select_nth_unstable_by_key(k, |&j| self.distance(i, j));
+                    others.truncate(k);
+                }
+                others.sort_unstable_by_key(|&j| self.distance(i, j));
+                others
+            })
+            .collect()
+    }
+
+    // The k / 4 closest nodes to each node in each quadrant around it
+    // completed with the closest remaining nodes when a quadrant doesn't have enough of them
+    pub fn quadrant_neighbours(&self, k: usize) -> Option<Vec<Vec<usize>>> {
+        let coords = self.coords()?;
+        let n = self.dimension;
+        let k = k.min(n.saturating_sub(1));
+        let per_quadrant = k / 4;
+
+        let quadrant = |i: usize, j: usize| {
+            let right = coords[j].0 >= coords[i].0;
+            let up = coords[j].1 >= coords[i].1;
+            (right as usize) * 2 + up as usize
+        };
+
+        let neighbours = (0..n)
+            .map(|i| {
+                let mut others: Vec<usize> = (0..n).filter(|&j| j != i).collect();
+                others.sort_unstable_by_key(|&j| self.distance(i, j));
+
+                let mut taken = [0; 4];
+                let mut chosen = Vec::with_capacity(k);
+                for &j in &others {
+                    let q = quadrant(i, j);
+                    if taken[q] < per_quadrant {
+                        taken[q] += 1;
+                        chosen.push(j);
+                    }
+                }
+                for &j in &others {
+                    if chosen.len() >= k {
+                        break;
+                    }
+                    if !chosen.contains(&j) {
+                        chosen.push(j);
+                    }
+                }
+
+                chosen.sort_unstable_by_key(|&j| self.distance(i, j));
+                chosen
+            })
+            .collect();
+
+        Some(neighbours)
+    }
 }
 
 fn upper_to_full(vec: &[usize], n: usize) -> Vec<u32> {
@@ -199,6 +268,7 @@ pub fn read_data(file_path: &str) -> Instance {
 
     use EdgeWeight::*;
     use EdgeWeightType::*;
+    let mut node_coords = None;
     let matrix = if let Explicit = weight_type {
         let matrix_type = instance
             .edge_weight
@@ -217,6 +287,7 @@ pub fn read_data(file_path: &str) -> Instance {
         } else {
             panic!("Something went wrong with the node coordinates type");
         };
+        node_coords = Some(coords.iter().map(|&(_, x, y)| (x, y)).collect());
         match weight_type {
             Euc2d => euc_2d(&coords),
             Ceil2d => ceil_2d(&coords),
@@ -230,5 +301,6 @@ pub fn read_data(file_path: &str) -> Instance {
         dimension,
         name,
         matrix,
+        coords: node_coords,
     }
 }
@@ -1,9 +1,14 @@
 use core::panic;
-use std::collections::{BTreeSet, VecDeque};
+use std::cmp::Ordering;
+use std::collections::{BTreeSet, BinaryHeap, VecDeque};
+use std::time::Instant;
 
 use instance_reader::Instance;
 
-use crate::lr::lr;
+use crate::lr::{
+    Graph, Node, SpecialNode, SubgradientConfig, can_improve, lr, reduced_cost_fixing, solve_root,
+    sorted_edge,
+};
 use crate::solution::Solution;
 
 fn convert_solution(s_in: &[Vec<usize>], cost: f64) -> Solution {
@@ -38,47 +43,422 @@ fn convert_solution(s_in: &[Vec<usize>], cost: f64) -> Solution {
     }
 }
 
-pub fn bnb_lr(instance: &Instance, upperbound: u32) -> Option<Solution> {
-    let mut tree = VecDeque::new();
+pub enum BnbResult {
+    /// A tour cheaper than the upperbound was found, it is the optimal solution
+    Improved(Solution),
+    /// No tour is cheaper than the upperbound
+    /// The incumbent, when there is one, is an optimal solution
+    /// The lowerbound is the smallest bound among the closed nodes and works as a certificate
+    NoTourBelow {
+        incumbent: Option<Solution>,
+        lowerbound: f64,
+    },
+}
+
+// Where edges are removed by their reduced cost before branching
+#[derive(Debug, Default, Clone, Copy)]
+pub enum ReducedCostFixing {
+    Off,
+    #[default]
+    Root,
+    EveryNode,
+}
+
+// How children are created from the node with the highest degree in the 1-tree
+#[derive(Debug, Default, Clone, Copy)]
+pub enum Branching {
+    // One child for each edge of the node, each forbidding that edge
+    #[default]
+    Forbid,
+    // Children forbid e1, require e1 and forbid e2, and require both
+    // so they don't share any solution
+    ForceForbid,
+}
+
+#[derive(Debug, Default, Clone, Copy)]
+pub enum SearchMode {
+    Bfs,
+    #[default]
+    Dfs,
+    // Node with the smallest lagrangian bound first
+    BestFirst,
+    // Dfs until the search finds a solution, then best first
+    DfsThenBestFirst,
+}
+
+#[derive(Default)]
+pub struct BnbConfig {
+    pub graph: Graph,
+    pub subgradient: SubgradientConfig,
+    // Children start from the parent multipliers, so they usually need a shorter schedule
+    pub child_subgradient: SubgradientConfig,
+    pub fixing: ReducedCostFixing,
+    pub branching: Branching,
+    pub search_mode: SearchMode,
+    pub special_node: SpecialNode,
+}
+
+// Orders nodes so the binary heap pops the smallest bound
+struct ByBound(Node);
+
+impl PartialEq for ByBound {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for ByBound {}
+
+impl PartialOrd for ByBound {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for ByBound {
+    fn cmp(&self, other: &Self) -> Ordering {
+        other.0.value.total_cmp(&self.0.value)
+    }
+}
+
+// Open nodes of the search
+struct Tree {
+    mode: SearchMode,
+    list: VecDeque<Node>,
+    heap: BinaryHeap<ByBound>,
+}
+
+impl Tree {
+    fn new(mode: SearchMode) -> Self {
+        Tree {
+            mode,
+            list: VecDeque::new(),
+            heap: BinaryHeap::new(),
+        }
+    }
+
+    fn push(&mut self, node: Node) {
+        match self.mode {
+            SearchMode::BestFirst => self.heap.push(ByBound(node)),
+            _ => self.list.push_back(node),
+        }
+    }
+
+    fn pop(&mut self) -> Option<Node> {
+        match self.mode {
+            SearchMode::Bfs => self.list.pop_front(),
+            SearchMode::Dfs | SearchMode::DfsThenBestFirst => self.list.pop_back(),
+            SearchMode::BestFirst => self.heap.pop().map(|node| node.0),
+        }
+    }
+
+    // Called when the search finds a solution
+    fn found_solution(&mut self) {
+        if let SearchMode::DfsThenBestFirst = self.mode {
+            self.mode = SearchMode::BestFirst;
+            self.heap.extend(self.list.drain(..).map(ByBound));
+        }
+    }
+
+    fn len(&self) -> usize {
+        self.list.len() + self.heap.len()
+    }
+
+    // Smallest bound among the open nodes
+    fn lowerbound(&self) -> f64 {
+        let list_min = self
+            .list
+            .iter()
+            .map(|node| node.value)
+            .fold(f64::INFINITY, f64::min);
+
+        match self.heap.peek() {
+            Some(node) => list_min.min(node.0.value),
+            None => list_min,
+        }
+    }
+}
+
+fn print_progress(branched: usize, tree: &Tree, closed_lowerbound: f64, upperbound: f64) {
+    let lowerbound = closed_lowerbound.min(tree.lowerbound()).min(upperbound);
+    let gap = 100.0 * (upperbound - lowerbound) / upperbound;
+    println!(
+        "Nodes: {branched}, open: {}, lower bound: {lowerbound:.2}, upper bound: {upperbound}, gap: {gap:.2}%",
+        tree.len()
+    );
+}
+
+// Whether the required arcs already connect i to j without visiting every node
+fn closes_subtour(node: &Node, (i, j): (usize, usize), n: usize) -> bool {
+    let next = |current: usize, previous: usize| {
+        node.required_arcs
+            .iter()
+            .map(|&(a, b)| {
+                if a == current {
+                    b
+                } else if b == current {
+                    a
+                } else {
+                    usize::MAX
+                }
+            })
+            .find(|&other| other != usize::MAX && other != previous)
+    };
+
+    let mut previous = i;
+    let mut current = j;
+    let mut visited = 2;
+    while let Some(other) = next(current, previous) {
+        if other == i {
+            return visited < n;
+        }
+        previous = current;
+        current = other;
+        visited += 1;
+    }
+
+    false
+}
+
+fn force_forbid_children(node: &Node, first: usize, neighbours: &[usize], n: usize) -> Vec<Node> {
+    let required = node
+        .required_arcs
+        .iter()
+        .filter(|&&(a, b)| a == first || b == first)
+        .count();
+    let free = neighbours
+        .iter()
+        .filter(|&&i| !node.is_required(first, i))
+        .take(2 - required);
+
+    let mut children = Vec::new();
+    let mut parent = node.clone();
+    for &i in free {
+        let edge = sorted_edge(first, i);
+
+        let mut child = parent.clone();
+        child.forbid(edge);
+        children.push(child);
+
+        // Every solution that requires this edge is infeasible
+        if closes_subtour(&parent, edge, n) {
+            return children;
+        }
+        parent.require(edge, n);
+    }
+    children.push(parent);
+
+    children
+}
+
+pub fn bnb_lr(
+    instance: &Instance,
+    upperbound: u32,
+    incumbent: Option<Solution>,
+    config: &BnbConfig,
+) -> BnbResult {
+    const PROGRESS_INTERVAL: usize = 1000;
+
+    let mut tree = Tree::new(config.search_mode);
     let mut upperbound = upperbound as f64;
     let mut best_node = None;
+    // Smallest bound among the nodes that were not branched
+    let mut lowerbound = f64::INFINITY;
+    let mut branched = 0;
+    let mut eliminated = 0;
+    let mut solved = 0;
+    let start = Instant::now();
+
+    // Solve root node, children keep its special node
+    tree.push(solve_root(
+        instance,
+        upperbound,
+        &config.graph,
+        &config.subgradient,
+        config.special_node,
+    ));
+    solved += 1;
 
-    // Solve root node
-    tree.push_back(lr(Default::default(), instance, upperbound));
+    let mut solve = |node, upperbound, subgradient| {
+        solved += 1;
+        lr(node, instance, upperbound, &config.graph, subgradient)
+    };
+
+    while let Some(mut node) = tree.pop() {
+        // The upperbound may have changed since this node was solved
+        if !can_improve(node.value, upperbound) {
+            lowerbound = lowerbound.min(node.value);
+            continue;
+        }
+
+        // Is node feasible?
+        if node.solution.is_some() {
+            // The 1-tree cost carries the rounding errors of the multipliers
+            upperbound = node.value.round();
+            lowerbound = lowerbound.min(node.value);
+            best_node = Some(node);
+            tree.found_solution();
+            print_progress(branched, &tree, lowerbound, upperbound);
+            continue;
+        }
+
+        match config.fixing {
+            ReducedCostFixing::Root if branched == 0 => {
+                eliminated = reduced_cost_fixing(&mut node, instance, upperbound);
+                println!("Reduced cost fixing removed {eliminated} edges at the root");
+            }
+            ReducedCostFixing::EveryNode => {
+                eliminated += reduced_cost_fixing(&mut node, instance, upperbound);
+            }
+            _ => (),
+        }
+        branched += 1;
+        if branched % PROGRESS_INTERVAL == 0 {
+            print_progress(branched, &tree, lowerbound.min(node.value), upperbound);
+        }
+
+        let (index_first, indeces) = node
+            .ban_from_child
+            .as_ref()
+            .expect("Unfeasible node without an edge to branch on");
+
+        if let Branching::ForceForbid = config.branching {
+            for child in force_forbid_children(&node, *index_first, indeces, instance.dimension) {
+                tree.push(solve(child, upperbound, &config.child_subgradient));
+            }
+            continue;
+        }
 
-    while let Some(node) = tree.pop_back() {
-        // If this is NONE than the upperbound is either wrong or is the optimal value
-        let (index_first, indeces) = node.ban_from_child.as_ref()?;
         for i in indeces {
             let mut new_node = node.clone();
-            let edge = if *index_first < *i {
-                (*index_first, *i)
-            } else {
-                (*i, *index_first)
-            };
+            let edge = sorted_edge(*index_first, *i);
 
             match new_node.forbidden_arcs.binary_search(&edge) {
                 Ok(_) => panic!("Duplicated edge on forbidden arcs"),
                 Err(idx) => new_node.forbidden_arcs.insert(idx, edge),
             }
 
-            new_node = lr(new_node, instance, upperbound);
+            tree.push(solve(new_node, upperbound, &config.child_subgradient));
+        }
+    }
 
-            if new_node.value < upperbound {
-                // Is node feasible?
-                if new_node.solution.is_some() {
-                    upperbound = node.value;
-                    best_node = Some(new_node);
-                } else {
-                    tree.push_back(new_node);
-                }
+    if let ReducedCostFixing::EveryNode = config.fixing {
+        println!("Reduced cost fixing removed {eliminated} edges");
+    }
+
+    let elapsed = start.elapsed().as_secs_f64();
+    println!(
+        "Solved {solved} nodes in {elapsed:.2}s ({:.0} nodes/s)",
+        solved as f64 / elapsed
+    );
+
+    match best_node {
+        Some(node) => BnbResult::Improved(convert_solution(&node.solution.unwrap(), node.value)),
+        None => BnbResult::NoTourBelow {
+            incumbent,
+            lowerbound,
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::heuristic::upperbound_tour;
+    use held_karp::{Objective, cost, held_karp};
+
+    fn assert_optimal(s: &Solution, instance: &Instance, optimum: u32) {
+        let mut nodes = s.sequence[..instance.dimension].to_vec();
+        nodes.sort_unstable();
+        assert!(nodes.into_iter().eq(0..instance.dimension));
+        assert_eq!(cost(instance, Objective::Tour, &s.sequence), s.value);
+        assert_eq!(s.value, optimum);
+    }
+
+    #[test]
+    fn bnb_lr_matches_held_karp() {
+        for name in ["burma14", "ulysses16", "gr17"] {
+            let instance = instance_reader::read_data(&format!("instances/{name}.tsp"));
+            let (_, optimum) = held_karp(&instance, Objective::Tour).unwrap();
+            let config = BnbConfig::default();
+
+            // Starting from the heuristic tour, as main does without an upperbound
+            let incumbent = upperbound_tour(&instance);
+            let s = match bnb_lr(&instance, incumbent.value, Some(incumbent), &config) {
+                BnbResult::Improved(s) => s,
+                BnbResult::NoTourBelow { incumbent, .. } => incumbent.unwrap(),
+            };
+            assert_optimal(&s, &instance, optimum);
+
+            // An upperbound right above the optimum only leaves the optimal tours
+            let improved = match bnb_lr(&instance, optimum + 1, None, &config) {
+                BnbResult::Improved(s) => Some(s),
+                BnbResult::NoTourBelow { .. } => None,
+            };
+            assert_optimal(&improved.expect(name), &instance, optimum);
+
+            // The optimum itself can't be improved and there is no tour to report
+            // The certificate shows that no tour costs less than the optimum
+            match bnb_lr(&instance, optimum, None, &config) {
+                BnbResult::NoTourBelow {
+                    incumbent: None,
+                    lowerbound,
+                } => assert!(!can_improve(lowerbound, optimum as f64), "{name}"),
+                _ => unreachable!("{name}"),
             }
         }
     }
 
-    let best_node = best_node?;
-    Some(convert_solution(
-        &best_node.solution.unwrap(),
-        best_node.value,
-    ))
+    #[test]
+    fn step_formulas_match_held_karp() {
+        use crate::lr::StepFormula;
+
+        let instance = instance_reader::read_data("instances/gr17.tsp");
+        let (_, optimum) = held_karp(&instance, Objective::Tour).unwrap();
+        for step_formula in [StepFormula::HeldWolfeCrowder, StepFormula::VolgenantJonker] {
+            let subgradient = SubgradientConfig {
+                step_formula,
+                ..Default::default()
+            };
+            let config = BnbConfig {
+                subgradient,
+                child_subgradient: SubgradientConfig {
+                    step_formula,
+                    ..SubgradientConfig::child()
+                },
+                ..Default::default()
+            };
+
+            let improved = match bnb_lr(&instance, optimum + 1, None, &config) {
+                BnbResult::Improved(s) => Some(s),
+                BnbResult::NoTourBelow { .. } => None,
+            };
+            assert_optimal(&improved.unwrap(), &instance, optimum);
+        }
+    }
+
+    // Each child solves a single tree from its parent multipliers
+    // The root is stopped early so that it branches
+    #[test]
+    fn child_schedule_finished_before_first_iteration() {
+        let instance = instance_reader::read_data("instances/burma14.tsp");
+        let (_, optimum) = held_karp(&instance, Objective::Tour).unwrap();
+        let config = BnbConfig {
+            subgradient: SubgradientConfig {
+                max_iter: Some(5),
+                ..Default::default()
+            },
+            child_subgradient: SubgradientConfig {
+                initial_step: 1e-4,
+                ..SubgradientConfig::child()
+            },
+            ..Default::default()
+        };
+
+        let improved = match bnb_lr(&instance, optimum + 1, None, &config) {
+            BnbResult::Improved(s) => Some(s),
+            BnbResult::NoTourBelow { .. } => None,
+        };
+        assert_optimal(&improved.unwrap(), &instance, optimum);
+    }
 }
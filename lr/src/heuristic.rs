@@ -0,0 +1,239 @@
+use instance_reader::Instance;
+
+use crate::solution::Solution;
+
+fn nearest_neighbour(instance: &Instance) -> Solution {
+    let n = instance.dimension;
+    let mut visited = vec![false; n];
+    let mut sequence = Vec::with_capacity(n + 1);
+    let mut value = 0;
+
+    let mut current = 0;
+    visited[0] = true;
+    sequence.push(0);
+
+    for _ in 1..n {
+        let next = (0..n)
+            .filter(|&j| !visited[j])
+            .min_by_key(|&j| instance.distance(current, j))
+            .unwrap();
+
+        value += instance.distance(current, next);
+        visited[next] = true;
+        sequence.push(next);
+        current = next;
+    }
+
+    value += instance.distance(current, 0);
+    sequence.push(0);
+
+    Solution { sequence, value }
+}
+
+// Closest nodes to each node, the moves only create edges towards them
+const NEIGHBOURS: usize = 10;
+
+// Sequence that starts and ends at node 0 with the position of every node
+// Edge k joins sequence[k] and sequence[k + 1]
+struct Tour<'a> {
+    instance: &'a Instance,
+    neighbours: Vec<Vec<usize>>,
+    sequence: Vec<usize>,
+    position: Vec<usize>,
+    value: u32,
+}
+
+impl<'a> Tour<'a> {
+    fn new(s: Solution, instance: &'a Instance) -> Self {
+        let mut position = vec![0; instance.dimension];
+        for (i, &node) in s.sequence.iter().enumerate().take(instance.dimension) {
+            position[node] = i;
+        }
+
+        Tour {
+            instance,
+            neighbours: instance.nearest_neighbours(NEIGHBOURS),
+            sequence: s.sequence,
+            position,
+            value: s.value,
+        }
+    }
+
+    fn c(&self, i: usize, j: usize) -> i32 {
+        self.instance.distance(i, j) as i32
+    }
+
+    // Edge that leaves the node
+    fn next_edge(&self, node: usize) -> usize {
+        self.position[node]
+    }
+
+    // Edge that enters the node, node 0 is entered by the last edge
+    fn previous_edge(&self, node: usize) -> usize {
+        match node {
+            0 => self.instance.dimension - 1,
+            _ => self.position[node] - 1,
+        }
+    }
+
+    fn update_positions(&mut self, start: usize, end: usize) {
+        for i in start..end {
+            self.position[self.sequence[i]] = i;
+        }
+    }
+
+    // Replaces edges e1 and e2 by reversing the nodes between them
+    fn two_opt_move(&mut self, e1: usize, e2: usize) -> bool {
+        let (e1, e2) = (e1.min(e2), e1.max(e2));
+        let s = &self.sequence;
+
+        let delta = self.c(s[e1], s[e2]) + self.c(s[e1 + 1], s[e2 + 1])
+            - self.c(s[e1], s[e1 + 1])
+            - self.c(s[e2], s[e2 + 1]);
+
+        if delta < 0 {
+            self.sequence[e1 + 1..=e2].reverse();
+            self.update_positions(e1 + 1, e2 + 1);
+            self.value = (self.value as i32 + delta) as u32;
+            return true;
+        }
+
+        false
+    }
+
+    // Moves the block that starts at position i between the ends of edge k
+    fn or_opt_move(&mut self, i: usize, block_size: usize, k: usize) -> bool {
+        // The edges around the block can't receive it
+        if (i - 1..i + block_size).contains(&k) {
+            return false;
+        }
+
+        let s = &self.sequence;
+        let block_end = s[i + block_size - 1];
+        let delta = self.c(s[i - 1], s[i + block_size])
+            - self.c(s[i - 1], s[i])
+            - self.c(block_end, s[i + block_size])
+            + self.c(s[k], s[i])
+            + self.c(block_end, s[k + 1])
+            - self.c(s[k], s[k + 1]);
+
+        if delta < 0 {
+            if i < k {
+                self.sequence[i..=k].rotate_left(block_size);
+                self.update_positions(i, k + 1);
+            } else {
+                self.sequence[(k + 1)..(i + block_size)].rotate_right(block_size);
+                self.update_positions(k + 1, i + block_size);
+            }
+            self.value = (self.value as i32 + delta) as u32;
+            return true;
+        }
+
+        false
+    }
+
+    // 2-opt moves that join each node to one of its neighbours, applied as soon as they improve
+    // An improving move creates an edge shorter than one of the edges it removes next to it,
+    // so the neighbours further than both edges of the node are skipped
+    fn two_opt(&mut self) -> bool {
+        let mut improved = false;
+
+        for a in 0..self.instance.dimension {
+            for index in 0..self.neighbours[a].len() {
+                let c = self.neighbours[a][index];
+                let (next, previous) = (self.next_edge(a), self.previous_edge(a));
+                let longest = self
+                    .c(a, self.sequence[next + 1])
+                    .max(self.c(self.sequence[previous], a));
+                if self.c(a, c) >= longest {
+                    break;
+                }
+
+                // Joins a and c through their successors or their predecessors
+                improved |= self.two_opt_move(next, self.next_edge(c))
+                    || self.two_opt_move(previous, self.previous_edge(c));
+            }
+        }
+
+        improved
+    }
+
+    // Or-opt moves that place the block next to a neighbour of one of its ends, in the same order
+    // Blocks never contain node 0, so it stays at both ends of the sequence
+    fn or_opt(&mut self, block_size: usize) -> bool {
+        let n = self.instance.dimension;
+        let mut improved = false;
+
+        for first in 1..n {
+            for index in 0..NEIGHBOURS.min(n - 1) {
+                let i = self.position[first];
+                if i + block_size > n {
+                    break;
+                }
+                let last = self.sequence[i + block_size - 1];
+
+                // After a neighbour of the first node or before a neighbour of the last one
+                let after = self.next_edge(self.neighbours[first][index]);
+                let before = self.previous_edge(self.neighbours[last][index]);
+                improved |= self.or_opt_move(i, block_size, after)
+                    || self.or_opt_move(i, block_size, before);
+            }
+        }
+
+        improved
+    }
+}
+
+// Nearest neighbour followed by 2-opt and Or-opt until a local optimum is reached
+// It is used to get an upperbound when none is supplied
+// The moves are searched on the nearest neighbours, so each pass checks O(n) moves
+pub fn upperbound_tour(instance: &Instance) -> Solution {
+    let s = nearest_neighbour(instance);
+
+    // Every tour is optimal with less than three nodes
+    if instance.dimension < 3 {
+        return s;
+    }
+
+    let mut tour = Tour::new(s, instance);
+    loop {
+        let improved = tour.two_opt() || tour.or_opt(1) || tour.or_opt(2) || tour.or_opt(3);
+
+        if !improved {
+            break;
+        }
+    }
+
+    Solution {
+        sequence: tour.sequence,
+        value: tour.value,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use held_karp::{Objective, cost};
+
+    #[test]
+    fn upperbound_tour_value_matches_sequence() {
+        for name in ["burma14", "att48", "kroA100", "a280"] {
+            let instance = instance_reader::read_data(&format!("instances/{name}.tsp"));
+            let nearest = nearest_neighbour(&instance);
+            let s = upperbound_tour(&instance);
+
+            assert_eq!(s.sequence.first(), Some(&0), "{name}");
+            assert_eq!(s.sequence.last(), Some(&0), "{name}");
+            let mut nodes = s.sequence[..instance.dimension].to_vec();
+            nodes.sort_unstable();
+            assert!(nodes.into_iter().eq(0..instance.dimension), "{name}");
+
+            assert_eq!(
+                cost(&instance, Objective::Tour, &s.sequence),
+                s.value,
+                "{name}"
+            );
+            assert!(s.value < nearest.value, "{name}");
+        }
+    }
+}
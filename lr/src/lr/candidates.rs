@@ -0,0 +1,53 @@
+use instance_reader::Instance;
+
+use super::prim::{self, EdgeState};
+
+// Sparse graph used to solve the 1-tree on large instances
+pub struct CandidateGraph {
+    // Sorted and symmetric adjacency lists
+    neighbours: Vec<Vec<usize>>,
+}
+
+impl CandidateGraph {
+    // The edges of a minimum spanning tree are always added so the graph is connected
+    pub fn from(neighbours: Vec<Vec<usize>>, instance: &Instance) -> Self {
+        let n = instance.dimension;
+        let mut adjacency = vec![vec![]; n];
+
+        for (i, list) in neighbours.into_iter().enumerate() {
+            for j in list {
+                adjacency[i].push(j);
+                adjacency[j].push(i);
+            }
+        }
+
+        // The tree leaves out the first node, the bound is still checked on the complete graph
+        // when another special node splits the candidate graph
+        let (_, tree) = prim::mst(instance, &vec![0.0; n], 0, |_, _| EdgeState::Free);
+        for (i, list) in tree.into_iter().enumerate() {
+            adjacency[i].extend(list);
+        }
+
+        for list in adjacency.iter_mut() {
+            list.sort_unstable();
+            list.dedup();
+        }
+
+        CandidateGraph {
+            neighbours: adjacency,
+        }
+    }
+
+    // Every edge (i, j) with i < j
+    pub fn edges(&self) -> impl Iterator<Item = (usize, usize)> + '_ {
+        self.neighbours.iter().enumerate().flat_map(|(i, list)| {
+            list.iter()
+                .skip_while(move |&&j| j < i)
+                .map(move |&j| (i, j))
+        })
+    }
+
+    pub fn edge_count(&self) -> usize {
+        self.neighbours.iter().map(|list| list.len()).sum::<usize>() / 2
+    }
+}
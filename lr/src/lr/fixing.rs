@@ -0,0 +1,77 @@
+use instance_reader::Instance;
+
+use super::prim::{self, EdgeMatrix, EdgeState};
+use super::{Node, can_improve, one_tree, sorted_edge};
+
+// Forbids every edge that can't be part of a solution better than the upperbound
+// An edge outside the 1-tree has to replace the most expensive edge in the cycle it creates,
+// when even the cheapest such exchange makes the bound reach the upperbound the edge is removed
+// Returns how many edges were removed
+pub fn reduced_cost_fixing(node: &mut Node, instance: &Instance, upperbound: f64) -> usize {
+    let n = instance.dimension;
+    if node.lambdas.is_empty() {
+        return 0;
+    }
+
+    let lambdas = &node.lambdas;
+    let special = node.special_node;
+    let edge_matrix = EdgeMatrix::from(&node.forbidden_arcs, &node.required_arcs, n);
+    let tree = prim::mst(instance, lambdas, special, |i, j| edge_matrix.get(i, j));
+    let (cost, edges) = one_tree(instance, lambdas, node, tree);
+    if !cost.is_finite() {
+        return 0;
+    }
+
+    let w = |i: usize, j: usize| instance.distance(i, j) as f64 - lambdas[i] - lambdas[j];
+
+    // Edges used for branching are kept so the children stay different from this node
+    let is_kept = |i: usize, j: usize| {
+        edge_matrix.get(i, j) != EdgeState::Free
+            || edges[i].contains(&j)
+            || node.ban_from_child.as_ref().is_some_and(|(first, others)| {
+                (*first == i && others.contains(&j)) || (*first == j && others.contains(&i))
+            })
+    };
+
+    let mut eliminated = Vec::new();
+
+    // Edges of the special node replace the most expensive of its two edges
+    let second = edges[special]
+        .iter()
+        .map(|&j| w(special, j))
+        .fold(f64::NEG_INFINITY, f64::max);
+    for j in (0..n).filter(|&j| j != special) {
+        if !is_kept(special, j) && !can_improve(cost + w(special, j) - second, upperbound) {
+            eliminated.push(sorted_edge(special, j));
+        }
+    }
+
+    // The other edges replace the most expensive edge in the tree path between their nodes
+    let mut path_max = vec![0.0; n];
+    let mut stack = Vec::with_capacity(n);
+    for i in (0..n).filter(|&i| i != special) {
+        path_max[i] = f64::NEG_INFINITY;
+        stack.push((i, i));
+        while let Some((u, parent)) = stack.pop() {
+            for &v in &edges[u] {
+                if v == special || v == parent {
+                    continue;
+                }
+                path_max[v] = f64::max(path_max[u], w(u, v));
+                stack.push((v, u));
+            }
+        }
+
+        for (j, max) in path_max.iter().enumerate().skip(i + 1) {
+            if j != special && !is_kept(i, j) && !can_improve(cost + w(i, j) - max, upperbound) {
+                eliminated.push((i, j));
+            }
+        }
+    }
+
+    let count = eliminated.len();
+    node.forbidden_arcs.extend(eliminated);
+    node.forbidden_arcs.sort_unstable();
+
+    count
+}
@@ -67,11 +67,27 @@ impl Pset {
     }
 }
 
-pub fn mst(mut pq: BinaryHeap<Edge>, n: usize) -> (f64, Vec<Vec<usize>>) {
+// Required edges are added before the queue, they must not form a cycle
+pub fn mst(
+    mut pq: BinaryHeap<Edge>,
+    required: impl Iterator<Item = Edge>,
+    n: usize,
+) -> (f64, Vec<Vec<usize>>) {
     let mut cost = 0.0;
     let mut pset = Pset::new(n);
     let mut edges = vec![vec![]; n];
 
+    for edge in required {
+        let (i, j) = (edge.i, edge.j);
+
+        if !pset.is_same(i, j) {
+            edges[i].push(j);
+            edges[j].push(i);
+            pset.union(i, j);
+            cost += edge.cost.0;
+        }
+    }
+
     while !pq.is_empty() {
         let edge = pq.pop().unwrap();
         let (i, j) = (edge.i, edge.j);
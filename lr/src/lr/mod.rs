@@ -1,40 +1,139 @@
+mod candidates;
+mod fixing;
 mod kruskal;
+mod prim;
+mod special;
+mod subgradient;
+
+pub use candidates::CandidateGraph;
+pub use fixing::reduced_cost_fixing;
+pub use special::{SpecialNode, solve_root};
+pub use subgradient::{StepFormula, SubgradientConfig};
 
 use instance_reader::Instance;
-use kruskal::{Edge, mst};
+use kruskal::Edge;
+use prim::{EdgeMatrix, EdgeState};
 use std::collections::BinaryHeap;
+use subgradient::Schedule;
+
+// Algorithm used to solve the spanning tree in each subgradient iteration
+#[derive(Debug, Default, Clone, Copy)]
+pub enum MstAlgorithm {
+    Kruskal,
+    #[default]
+    Prim,
+}
+
+// Graph where the spanning tree is solved
+pub enum Graph {
+    Complete(MstAlgorithm),
+    // Kruskal over the candidate edges, the final bound is checked on the complete graph
+    Sparse(CandidateGraph),
+}
+
+impl Default for Graph {
+    fn default() -> Self {
+        Graph::Complete(Default::default())
+    }
+}
 
 #[derive(Debug, Default, Clone)]
 pub struct Node {
     pub forbidden_arcs: Vec<(usize, usize)>,
+    pub required_arcs: Vec<(usize, usize)>,
     pub lambdas: Vec<f64>,
     pub solution: Option<Vec<Vec<usize>>>,
     pub ban_from_child: Option<(usize, Vec<usize>)>,
     pub value: f64,
+    // Node left out of the spanning tree and connected by its two cheapest edges
+    // Its multiplier stays at 0 since its degree is always 2
+    pub special_node: usize,
+}
+
+pub fn sorted_edge(i: usize, j: usize) -> (usize, usize) {
+    if i < j { (i, j) } else { (j, i) }
+}
+
+impl Node {
+    // Arcs are kept sorted with the smallest index first
+    pub fn forbid(&mut self, edge: (usize, usize)) {
+        if let Err(idx) = self.forbidden_arcs.binary_search(&edge) {
+            self.forbidden_arcs.insert(idx, edge);
+        }
+    }
+
+    // A node with two required edges can't use any other edge, so those are forbidden
+    pub fn require(&mut self, edge: (usize, usize), dimension: usize) {
+        if let Err(idx) = self.required_arcs.binary_search(&edge) {
+            self.required_arcs.insert(idx, edge);
+        }
+
+        for v in [edge.0, edge.1] {
+            let required = self
+                .required_arcs
+                .iter()
+                .filter(|&&(a, b)| a == v || b == v)
+                .count();
+            if required < 2 {
+                continue;
+            }
+
+            for u in (0..dimension).filter(|&u| u != v) {
+                let other = sorted_edge(u, v);
+                if self.required_arcs.binary_search(&other).is_err() {
+                    self.forbid(other);
+                }
+            }
+        }
+    }
+
+    pub fn is_required(&self, i: usize, j: usize) -> bool {
+        self.required_arcs.binary_search(&sorted_edge(i, j)).is_ok()
+    }
+}
+
+// Arcs of the special node are skipped, the remaining ones keep their order
+fn without_special(
+    arcs: &[(usize, usize)],
+    special: usize,
+) -> impl Iterator<Item = &(usize, usize)> {
+    arcs.iter()
+        .filter(move |&&(a, b)| a != special && b != special)
+}
+
+// Other end of the arcs of the special node, in increasing order
+fn special_neighbours(arcs: &[(usize, usize)], special: usize) -> impl Iterator<Item = usize> + '_ {
+    arcs.iter().filter_map(move |&(a, b)| {
+        if a == special {
+            Some(b)
+        } else if b == special {
+            Some(a)
+        } else {
+            None
+        }
+    })
 }
 
 fn build_priority_queue(
     instance: &Instance,
     lambdas: &[f64],
     forbidden_arcs: &[(usize, usize)],
+    special: usize,
 ) -> BinaryHeap<Edge> {
     let n = instance.dimension;
-    // The first node will not be part of kruskal
-    let final_size = (n - 1) * (n - 2) / 2 - forbidden_arcs.len();
+    // The special node will not be part of kruskal
+    let final_size = ((n - 1) * (n - 2) / 2).saturating_sub(forbidden_arcs.len());
     let mut edges = Vec::with_capacity(final_size);
 
-    let mut forbidden_arcs_it = forbidden_arcs
-        .iter()
-        .skip_while(|(a, _)| *a == 0)
-        .peekable();
-
-    for i in 1..n {
-        for j in i + 1..n {
-            if let Some(&&(a, b)) = forbidden_arcs_it.peek() {
-                if (a, b) == (i, j) {
-                    forbidden_arcs_it.next();
-                    continue;
-                }
+    let mut forbidden_arcs_it = without_special(forbidden_arcs, special).peekable();
+
+    for i in (0..n).filter(|&i| i != special) {
+        for j in (i + 1..n).filter(|&j| j != special) {
+            if let Some(&&(a, b)) = forbidden_arcs_it.peek()
+                && (a, b) == (i, j)
+            {
+                forbidden_arcs_it.next();
+                continue;
             }
 
             let cost = instance.distance(i, j) as f64 - lambdas[i] - lambdas[j];
@@ -46,52 +145,143 @@ fn build_priority_queue(
     edges.into()
 }
 
-fn closest_to_first_node(
+fn build_sparse_priority_queue(
     instance: &Instance,
     lambdas: &[f64],
+    candidates: &CandidateGraph,
     forbidden_arcs: &[(usize, usize)],
+    special: usize,
+) -> BinaryHeap<Edge> {
+    let mut edges = Vec::with_capacity(candidates.edge_count());
+
+    // The special node will not be part of kruskal
+    for (i, j) in candidates
+        .edges()
+        .filter(|&(i, j)| i != special && j != special)
+    {
+        if forbidden_arcs.binary_search(&(i, j)).is_ok() {
+            continue;
+        }
+
+        let cost = instance.distance(i, j) as f64 - lambdas[i] - lambdas[j];
+
+        edges.push(Edge::from(cost, i, j));
+    }
+
+    edges.into()
+}
+
+fn closest_to_special_node(
+    instance: &Instance,
+    lambdas: &[f64],
+    special: usize,
+    forbidden_arcs: &[(usize, usize)],
+    required_arcs: &[(usize, usize)],
 ) -> (usize, usize, f64) {
-    let mut lowest_index_1 = 0;
-    let mut lowest_index_2 = 0;
+    let mut lowest_index_1 = special;
+    let mut lowest_index_2 = special;
     let mut lowest1 = f64::INFINITY;
     let mut lowest2 = f64::INFINITY;
+    // Required edges are always chosen before the others
+    let mut lowest_required_1 = false;
+    let mut lowest_required_2 = false;
 
-    let mut forbidden_arcs_it = forbidden_arcs
-        .iter()
-        .take_while(|(a, _)| *a == 0)
-        .peekable();
+    let mut forbidden_it = special_neighbours(forbidden_arcs, special).peekable();
+    let mut required_it = special_neighbours(required_arcs, special).peekable();
 
-    for (i, lambda) in lambdas.iter().enumerate().skip(1) {
-        if let Some(&&(_, b)) = forbidden_arcs_it.peek() {
-            if b == i {
-                forbidden_arcs_it.next();
-                continue;
-            }
+    for (i, lambda) in lambdas.iter().enumerate().filter(|&(i, _)| i != special) {
+        if forbidden_it.next_if_eq(&i).is_some() {
+            continue;
         }
 
-        // Lambda[0] is always 0 so we ignore it
-        let cost = instance.distance(0, i) as f64 - lambda;
+        let required = required_it.next_if_eq(&i).is_some();
+
+        // The multiplier of the special node is always 0 so we ignore it
+        let cost = instance.distance(special, i) as f64 - lambda;
+        let is_lower = |lowest: f64, lowest_required: bool| {
+            (required && !lowest_required) || (required == lowest_required && cost < lowest)
+        };
 
-        if cost < lowest1 {
+        if is_lower(lowest1, lowest_required_1) {
             // Shift the value to second
             lowest2 = lowest1;
             lowest_index_2 = lowest_index_1;
+            lowest_required_2 = lowest_required_1;
 
             lowest1 = cost;
             lowest_index_1 = i;
-        } else if cost < lowest2 {
+            lowest_required_1 = required;
+        } else if is_lower(lowest2, lowest_required_2) {
             lowest2 = cost;
             lowest_index_2 = i;
+            lowest_required_2 = required;
         }
     }
 
     (lowest_index_1, lowest_index_2, lowest1 + lowest2)
 }
 
-pub fn lr(node: Node, instance: &Instance, upperbound: f64) -> Node {
-    const MAX_ITER: u32 = 30;
-    const MIN_EPS: f64 = 1e-5;
+// Assuming that the feasible solution cost is integer
+// a node can only lead to a better solution when its bound is
+// lower than upperbound - 1 (with some slack)
+pub fn can_improve(value: f64, upperbound: f64) -> bool {
+    upperbound - value >= 1.0 - 1e-2
+}
 
+// Adds the special node to the spanning tree of the remaining nodes
+fn one_tree(
+    instance: &Instance,
+    lambdas: &[f64],
+    node: &Node,
+    (mut cost, mut edges): (f64, Vec<Vec<usize>>),
+) -> (f64, Vec<Vec<usize>>) {
+    // Adjust cost
+    cost += 2.0 * lambdas.iter().sum::<f64>();
+
+    // Reinsert special node into solution
+    let special = node.special_node;
+    let (first, second, added_cost) = closest_to_special_node(
+        instance,
+        lambdas,
+        special,
+        &node.forbidden_arcs,
+        &node.required_arcs,
+    );
+    edges[special].push(first);
+    edges[first].push(special);
+    edges[special].push(second);
+    edges[second].push(special);
+    cost += added_cost;
+
+    (cost, edges)
+}
+
+fn subgradients(edges: &[Vec<usize>]) -> (Vec<i32>, u32) {
+    let subgradients = edges
+        .iter()
+        .map(|x| 2 - x.len() as i32)
+        .collect::<Vec<i32>>();
+    let sum_subgradient = subgradients.iter().map(|x| (x * x) as u32).sum::<u32>();
+
+    (subgradients, sum_subgradient)
+}
+
+pub fn lr(
+    node: Node,
+    instance: &Instance,
+    upperbound: f64,
+    graph: &Graph,
+    config: &SubgradientConfig,
+) -> Node {
+    let parent_value = node.value;
+    // Only the arcs are used from this copy
+    let arcs = Node {
+        forbidden_arcs: node.forbidden_arcs.clone(),
+        required_arcs: node.required_arcs.clone(),
+        special_node: node.special_node,
+        ..Default::default()
+    };
+    let special = arcs.special_node;
     let mut best_node = node;
     let mut lambdas = best_node.lambdas.clone();
 
@@ -102,83 +292,128 @@ pub fn lr(node: Node, instance: &Instance, upperbound: f64) -> Node {
 
     let mut best_edges = Vec::new();
 
-    let mut iter_not_improved = 0;
-    let mut eps = 1.0;
-    while eps > MIN_EPS {
-        // Solve MST without the first node
-        let (mut cost, mut edges) = mst(
-            build_priority_queue(instance, &lambdas, &best_node.forbidden_arcs),
+    // Arcs don't change during the subgradient so this is built only once
+    let edge_matrix = match graph {
+        Graph::Complete(MstAlgorithm::Prim) => Some(EdgeMatrix::from(
+            &arcs.forbidden_arcs,
+            &arcs.required_arcs,
             instance.dimension,
-        );
-        // Adjust cost
-        cost += 2.0 * lambdas.iter().sum::<f64>();
-
-        // Reinsert first node into solution
-        let (first, second, added_cost) =
-            closest_to_first_node(instance, &lambdas, &best_node.forbidden_arcs);
-        edges[0].push(first);
-        edges[first].push(0);
-        edges[0].push(second);
-        edges[second].push(0);
-        cost += added_cost;
-
-        let subgradients = edges
-            .iter()
-            .map(|x| 2 - x.len() as i32)
-            .collect::<Vec<i32>>();
-        let sum_subgradient = subgradients.iter().map(|x| (x * x) as u32).sum::<u32>();
+        )),
+        _ => None,
+    };
+
+    // Required edges that are part of kruskal
+    let required_edges = |lambdas: &[f64]| {
+        without_special(&arcs.required_arcs, special)
+            .map(|&(i, j)| {
+                Edge::from(
+                    instance.distance(i, j) as f64 - lambdas[i] - lambdas[j],
+                    i,
+                    j,
+                )
+            })
+            .collect::<Vec<Edge>>()
+    };
+
+    // Solve MST without the special node
+    let solve = |lambdas: &[f64]| {
+        let tree = match (graph, &edge_matrix) {
+            (Graph::Sparse(candidates), _) => kruskal::mst(
+                build_sparse_priority_queue(
+                    instance,
+                    lambdas,
+                    candidates,
+                    &arcs.forbidden_arcs,
+                    special,
+                ),
+                required_edges(lambdas).into_iter(),
+                instance.dimension,
+            ),
+            (_, Some(edge_matrix)) => {
+                prim::mst(instance, lambdas, special, |i, j| edge_matrix.get(i, j))
+            }
+            (_, None) => kruskal::mst(
+                build_priority_queue(instance, lambdas, &arcs.forbidden_arcs, special),
+                required_edges(lambdas).into_iter(),
+                instance.dimension,
+            ),
+        };
+        one_tree(instance, lambdas, &arcs, tree)
+    };
+
+    let mut schedule = Schedule::new(config, instance.dimension);
+    // At least one tree is solved, so there is always an edge to branch on
+    loop {
+        let (cost, edges) = solve(&lambdas);
+
+        let (subgradients, sum_subgradient) = subgradients(&edges);
         // Assign feasible solution and end algorithm
         if sum_subgradient == 0 {
             best_node.value = cost;
+            best_node.lambdas = lambdas;
             best_node.solution = Some(edges);
             break;
         }
 
         // Check if there is an improvement
-        if cost > best_node.value {
-            best_node.value = cost;
+        // Children start with the parent value, so the first tree is kept in case nothing improves
+        let improved = cost > best_node.value;
+        if improved || best_edges.is_empty() {
+            best_node.value = best_node.value.max(cost);
             best_node.lambdas = lambdas.clone();
             best_edges = edges;
-            iter_not_improved = 0;
-        } else {
-            iter_not_improved += 1;
-            if iter_not_improved >= MAX_ITER {
-                iter_not_improved = 0;
-                eps /= 2.0;
-            }
         }
+        schedule.update(improved);
 
-        let mi = (eps * (upperbound - cost)) / sum_subgradient as f64;
-
-        for (index, lambda) in lambdas.iter_mut().enumerate() {
-            *lambda += mi * subgradients[index] as f64;
+        // There is no better solution than the current upperbound in this node
+        // The subgradient stops early, so the bound is only good enough to prune the node
+        if !can_improve(best_node.value, upperbound) || schedule.finished() {
+            break;
         }
 
-        // Assuming that the feasible solution cost is integer
-        // When this cost is higher than upperbound - 1 (with some slack)
-        // there will be no better solution than the current upperbound
-        if upperbound - cost < 1.0 - 1e-2 {
-            // this will also cause a problem when the upperbound in the user input is the optimal value
-            // this optimization will make so that the optimal value can't be proven
-            best_node.value = f64::INFINITY;
-            return best_node;
+        schedule.apply(&mut lambdas, &subgradients, cost, upperbound);
+    }
+
+    // A tree on the sparse graph may not be minimum on the complete graph
+    // so the bound is recomputed there with the final multipliers to make sure it's valid
+    if let Graph::Sparse(_) = graph {
+        let tree = prim::mst(instance, &best_node.lambdas, special, |i, j| {
+            if arcs
+                .forbidden_arcs
+                .binary_search(&sorted_edge(i, j))
+                .is_ok()
+            {
+                EdgeState::Forbidden
+            } else if arcs.is_required(i, j) {
+                EdgeState::Required
+            } else {
+                EdgeState::Free
+            }
+        });
+        let (cost, edges) = one_tree(instance, &best_node.lambdas, &arcs, tree);
+
+        if subgradients(&edges).1 == 0 {
+            best_node.value = cost;
+            best_node.solution = Some(edges);
+        } else {
+            best_node.value = parent_value.max(cost);
+            best_node.solution = None;
+            best_edges = edges;
         }
     }
 
-    best_node.ban_from_child = if best_node.solution.is_none() {
-        // if best edges is empty at this point something went wrong
-        // anyways if the code panics at this point it's mostly likely that the upperbound is way
-        // higher than the actual optimal value, mostly you would want to treat this instead of unwraping
-        Some(
-            best_edges
-                .into_iter()
-                .enumerate()
-                .max_by(|(_, a), (_, b)| a.len().cmp(&b.len()))
-                .unwrap(),
-        )
-    } else {
-        None
-    };
+    best_node.ban_from_child =
+        if best_node.solution.is_none() && can_improve(best_node.value, upperbound) {
+            Some(
+                best_edges
+                    .into_iter()
+                    .enumerate()
+                    .max_by(|(_, a), (_, b)| a.len().cmp(&b.len()))
+                    .unwrap(),
+            )
+        } else {
+            None
+        };
 
     best_node
 }
@@ -0,0 +1,112 @@
+use instance_reader::Instance;
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum EdgeState {
+    #[default]
+    Free,
+    Forbidden,
+    Required,
+}
+
+// Dense version of the forbidden and required arcs so each edge can be checked in constant time
+pub struct EdgeMatrix {
+    matrix: Vec<EdgeState>,
+    dimension: usize,
+}
+
+impl EdgeMatrix {
+    pub fn from(
+        forbidden_arcs: &[(usize, usize)],
+        required_arcs: &[(usize, usize)],
+        dimension: usize,
+    ) -> Self {
+        let mut matrix = vec![EdgeState::Free; dimension * dimension];
+        let arcs = forbidden_arcs
+            .iter()
+            .map(|arc| (arc, EdgeState::Forbidden))
+            .chain(required_arcs.iter().map(|arc| (arc, EdgeState::Required)));
+        for (&(i, j), state) in arcs {
+            matrix[(i * dimension) + j] = state;
+            matrix[(j * dimension) + i] = state;
+        }
+
+        EdgeMatrix { matrix, dimension }
+    }
+
+    #[inline]
+    pub fn get(&self, i: usize, j: usize) -> EdgeState {
+        self.matrix[(i * self.dimension) + j]
+    }
+}
+
+// O(n²) prim over the complete graph without the special node of the 1-tree
+// Required edges are always chosen first, they must not form a cycle
+// If forbidden arcs disconnect the graph the cost is infinite
+pub fn mst(
+    instance: &Instance,
+    lambdas: &[f64],
+    special: usize,
+    edge_state: impl Fn(usize, usize) -> EdgeState,
+) -> (f64, Vec<Vec<usize>>) {
+    let n = instance.dimension;
+    let mut edges = vec![vec![]; n];
+    let mut cost = 0.0;
+
+    if n < 3 {
+        return (cost, edges);
+    }
+
+    let mut in_tree = vec![false; n];
+    // Selection key and cost of the edge to the tree, they only differ on required edges
+    let mut key = vec![f64::INFINITY; n];
+    let mut key_cost = vec![f64::INFINITY; n];
+    let mut parent = vec![usize::MAX; n];
+
+    in_tree[special] = true;
+    let mut current = if special == 0 { 1 } else { 0 };
+    in_tree[current] = true;
+
+    for _ in 2..n {
+        let mut next = usize::MAX;
+        let mut next_key = f64::INFINITY;
+
+        for j in 0..n {
+            if in_tree[j] {
+                continue;
+            }
+
+            let state = edge_state(current, j);
+            if state != EdgeState::Forbidden {
+                let edge_cost =
+                    instance.distance(current, j) as f64 - lambdas[current] - lambdas[j];
+                let edge_key = if state == EdgeState::Required {
+                    f64::NEG_INFINITY
+                } else {
+                    edge_cost
+                };
+                if edge_key < key[j] {
+                    key[j] = edge_key;
+                    key_cost[j] = edge_cost;
+                    parent[j] = current;
+                }
+            }
+
+            if next == usize::MAX || key[j] < next_key {
+                next = j;
+                next_key = key[j];
+            }
+        }
+
+        if parent[next] == usize::MAX {
+            return (f64::INFINITY, edges);
+        }
+
+        in_tree[next] = true;
+        edges[next].push(parent[next]);
+        edges[parent[next]].push(next);
+        cost += key_cost[next];
+        current = next;
+    }
+
+    (cost, edges)
+}
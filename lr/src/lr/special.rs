@@ -0,0 +1,75 @@
+use instance_reader::Instance;
+
+use super::prim::{self, EdgeState};
+use super::{Graph, Node, SubgradientConfig, lr, one_tree};
+
+// Node left out of the spanning tree of the 1-tree
+// Every choice gives a valid bound, but the bound after the subgradient changes with it
+#[derive(Debug, Clone, Copy)]
+pub enum SpecialNode {
+    Fixed(usize),
+    // Node with the highest 1-tree bound without multipliers
+    MaxBound,
+    // Solves the root with this many of the nodes with the highest bound and keeps the best one
+    Try(usize),
+}
+
+impl Default for SpecialNode {
+    fn default() -> Self {
+        SpecialNode::Fixed(0)
+    }
+}
+
+// Nodes sorted by decreasing 1-tree bound without multipliers when each one is the special node
+// This solves one spanning tree per node, so it's O(n³)
+fn by_initial_bound(instance: &Instance) -> Vec<usize> {
+    let n = instance.dimension;
+    let lambdas = vec![0.0; n];
+
+    let mut bounds = (0..n)
+        .map(|special| {
+            let node = Node {
+                special_node: special,
+                ..Default::default()
+            };
+            let tree = prim::mst(instance, &lambdas, special, |_, _| EdgeState::Free);
+            (one_tree(instance, &lambdas, &node, tree).0, special)
+        })
+        .collect::<Vec<(f64, usize)>>();
+    bounds.sort_by(|a, b| b.0.total_cmp(&a.0));
+
+    bounds.into_iter().map(|(_, special)| special).collect()
+}
+
+// Chooses the special node of the whole search and solves the root node with it
+pub fn solve_root(
+    instance: &Instance,
+    upperbound: f64,
+    graph: &Graph,
+    config: &SubgradientConfig,
+    special: SpecialNode,
+) -> Node {
+    let candidates = match special {
+        SpecialNode::Fixed(i) => {
+            assert!(i < instance.dimension, "Special node out of range");
+            vec![i]
+        }
+        SpecialNode::MaxBound => by_initial_bound(instance).into_iter().take(1).collect(),
+        SpecialNode::Try(count) => by_initial_bound(instance)
+            .into_iter()
+            .take(count.max(1))
+            .collect(),
+    };
+
+    candidates
+        .into_iter()
+        .map(|special_node| {
+            let root = Node {
+                special_node,
+                ..Default::default()
+            };
+            lr(root, instance, upperbound, graph, config)
+        })
+        .max_by(|a, b| a.value.total_cmp(&b.value))
+        .unwrap()
+}
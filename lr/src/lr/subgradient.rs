@@ -0,0 +1,144 @@
+// Formula used to compute the step size on each iteration
+#[derive(Debug, Default, Clone, Copy)]
+pub enum StepFormula {
+    // eps * (upperbound - cost) / |d|², eps is halved when the bound stops improving
+    #[default]
+    Polyak,
+    // Same step as Polyak, but eps is halved on a fixed number of iterations
+    HeldWolfeCrowder,
+    // Step decreasing on a second order schedule that reaches zero at the last iteration
+    // The first step is Polyak's, the schedule only scales it afterwards
+    VolgenantJonker,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct SubgradientConfig {
+    pub initial_step: f64,
+    // Iterations before halving the step
+    pub patience: u32,
+    pub min_step: f64,
+    pub max_iter: Option<u32>,
+    pub step_formula: StepFormula,
+    // Weight of the previous direction, the direction is the subgradient itself when it's 0
+    pub smoothing: f64,
+}
+
+impl Default for SubgradientConfig {
+    fn default() -> Self {
+        SubgradientConfig {
+            initial_step: 1.0,
+            patience: 30,
+            min_step: 1e-5,
+            max_iter: None,
+            step_formula: StepFormula::default(),
+            smoothing: 0.0,
+        }
+    }
+}
+
+impl SubgradientConfig {
+    // Shorter schedule for nodes that start from their parent multipliers
+    pub fn child() -> Self {
+        SubgradientConfig {
+            initial_step: 1.0,
+            patience: 5,
+            min_step: 1e-3,
+            max_iter: Some(50),
+            ..Default::default()
+        }
+    }
+}
+
+pub struct Schedule<'a> {
+    config: &'a SubgradientConfig,
+    eps: f64,
+    iter: u32,
+    iter_not_improved: u32,
+    // Length of the Volgenant-Jonker schedule
+    vj_iterations: u32,
+    // Step of the first iteration, scaled by the Volgenant-Jonker schedule
+    vj_first_step: Option<f64>,
+    direction: Vec<f64>,
+}
+
+impl<'a> Schedule<'a> {
+    pub fn new(config: &'a SubgradientConfig, dimension: usize) -> Self {
+        let vj_iterations = config.max_iter.unwrap_or(2 * dimension as u32).max(3);
+
+        Schedule {
+            config,
+            eps: config.initial_step,
+            iter: 0,
+            iter_not_improved: 0,
+            vj_iterations,
+            vj_first_step: None,
+            direction: vec![0.0; dimension],
+        }
+    }
+
+    pub fn finished(&self) -> bool {
+        if let Some(max_iter) = self.config.max_iter
+            && self.iter >= max_iter
+        {
+            return true;
+        }
+
+        match self.config.step_formula {
+            StepFormula::VolgenantJonker => self.iter >= self.vj_iterations,
+            _ => self.eps <= self.config.min_step,
+        }
+    }
+
+    // Must be called once per iteration with whether the bound improved
+    pub fn update(&mut self, improved: bool) {
+        self.iter += 1;
+
+        if improved {
+            self.iter_not_improved = 0;
+        } else {
+            self.iter_not_improved += 1;
+        }
+
+        let halve = match self.config.step_formula {
+            StepFormula::Polyak => self.iter_not_improved >= self.config.patience,
+            StepFormula::HeldWolfeCrowder => self.iter.is_multiple_of(self.config.patience),
+            StepFormula::VolgenantJonker => false,
+        };
+
+        if halve {
+            self.iter_not_improved = 0;
+            self.eps /= 2.0;
+        }
+    }
+
+    // Smooths the subgradient with the previous direction and moves the multipliers along it
+    pub fn apply(&mut self, lambdas: &mut [f64], subgradients: &[i32], cost: f64, upperbound: f64) {
+        let alpha = self.config.smoothing;
+        for (d, g) in self.direction.iter_mut().zip(subgradients) {
+            *d = (1.0 - alpha) * *g as f64 + alpha * *d;
+        }
+        let norm = self.direction.iter().map(|d| d * d).sum::<f64>();
+        if norm == 0.0 {
+            return;
+        }
+
+        let polyak = (self.eps * (upperbound - cost)) / norm;
+        let mi = match self.config.step_formula {
+            StepFormula::Polyak | StepFormula::HeldWolfeCrowder => polyak,
+            StepFormula::VolgenantJonker => {
+                // self.iter was already incremented, so k starts at 1
+                let k = self.iter as f64;
+                let m = self.vj_iterations as f64;
+                // Scaled by the gap and the subgradient of the first tree, like Polyak's step
+                let t1 = *self.vj_first_step.get_or_insert(polyak);
+
+                t1 * ((k - 1.0) * (2.0 * m - 5.0) / (2.0 * (m - 1.0)) - (k - 2.0)
+                    + (k - 1.0) * (k - 2.0) / (2.0 * (m - 1.0) * (m - 2.0)))
+            }
+        };
+
+        for (lambda, d) in lambdas.iter_mut().zip(&self.direction) {
+            *lambda += mi * d;
+        }
+    }
+}
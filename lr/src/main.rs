@@ -1,33 +1,298 @@
 mod bnb_lr;
+mod heuristic;
 mod lr;
 mod solution;
 
 use std::env;
 
-use bnb_lr::bnb_lr;
+use bnb_lr::{BnbConfig, BnbResult, Branching, ReducedCostFixing, SearchMode, bnb_lr};
+use heuristic::upperbound_tour;
+use lr::{
+    CandidateGraph, Graph, MstAlgorithm, SpecialNode, StepFormula, SubgradientConfig, can_improve,
+    solve_root,
+};
+
+fn usage() {
+    eprintln!("Not enough arguments, you need to pass the path to an instance");
+    eprintln!(
+        "Use cargo run /path/to/instance UPPERBOUND or ./path/to/bin /path/to/instance UPPERBOUND"
+    );
+    eprintln!("Upperbound is optional, a heuristic tour is used when it is not given");
+    eprintln!("Options:");
+    eprintln!("  --mst ALGORITHM    kruskal or prim (default)");
+    eprintln!("  --graph GRAPH      complete (default), nearest or quadrant");
+    eprintln!("  --candidates K     neighbours per node on sparse graphs, default is 10");
+    eprintln!("  --lowerbound       only compute the Held-Karp lower bound");
+    eprintln!("  --fixing WHERE     reduced cost fixing: off, root (default) or nodes");
+    eprintln!("  --branching RULE   forbid (default) or force (force/forbid pairs)");
+    eprintln!("  --search MODE      dfs (default), bfs, best or dfs-best");
+    eprintln!("  --special NODE     node left out of the spanning tree, default is 0");
+    eprintln!("                     max uses the node with the best bound without multipliers");
+    eprintln!(
+        "                     try:K solves the root with the K best nodes and keeps the best"
+    );
+    eprintln!("Subgradient options:");
+    eprintln!(
+        "  --step-formula F   polyak (default), hwc (Held-Wolfe-Crowder) or vj (Volgenant-Jonker)"
+    );
+    eprintln!("  --initial-step X   initial step factor, default is 1.0");
+    eprintln!("  --patience N       iterations before halving the step, default is 30");
+    eprintln!("  --min-step X       stop when the step factor is smaller, default is 1e-5");
+    eprintln!("                     vj doesn't halve the step and rejects both options");
+    eprintln!("  --max-iter N       maximum number of iterations per node, vj runs 2n by default");
+    eprintln!("  --smoothing A      weight of the previous direction in [0, 1), default is 0");
+    eprintln!("The options above apply to the root node, children are configured with:");
+    eprintln!("  --child-initial-step X   default is 1.0");
+    eprintln!("  --child-patience N       default is 5");
+    eprintln!("  --child-min-step X       default is 1e-3");
+    eprintln!("  --child-max-iter N       default is 50");
+}
+
+fn parse_value<T: std::str::FromStr>(value: Option<String>) -> Option<T> {
+    value.and_then(|value| value.parse().ok())
+}
 
 fn main() {
-    let args: Vec<String> = env::args().collect();
+    let mut args = Vec::new();
+    let mut mst_algorithm = MstAlgorithm::default();
+    let mut graph_type = String::from("complete");
+    let mut candidates = 10;
+    let mut only_lowerbound = false;
+    let mut config = SubgradientConfig::default();
+    let mut child_config = SubgradientConfig::child();
+    let mut fixing = ReducedCostFixing::default();
+    let mut branching = Branching::default();
+    let mut search_mode = SearchMode::default();
+    let mut special_node = SpecialNode::default();
+    // --patience and --min-step, which only apply to the halving schedules
+    let mut halving_options = false;
 
-    if args.len() != 3 {
-        eprintln!("Not enough arguments, you need to pass the path to an instance");
+    let mut args_it = env::args();
+    while let Some(arg) = args_it.next() {
+        match arg.as_str() {
+            "--mst" => {
+                mst_algorithm = match args_it.next().as_deref() {
+                    Some("kruskal") => MstAlgorithm::Kruskal,
+                    Some("prim") => MstAlgorithm::Prim,
+                    _ => return usage(),
+                }
+            }
+            "--graph" => match args_it.next() {
+                Some(graph) => graph_type = graph,
+                None => return usage(),
+            },
+            "--candidates" => match parse_value(args_it.next()) {
+                Some(k) if k > 0 => candidates = k,
+                _ => return usage(),
+            },
+            "--lowerbound" => only_lowerbound = true,
+            "--fixing" => {
+                fixing = match args_it.next().as_deref() {
+                    Some("off") => ReducedCostFixing::Off,
+                    Some("root") => ReducedCostFixing::Root,
+                    Some("nodes") => ReducedCostFixing::EveryNode,
+                    _ => return usage(),
+                }
+            }
+            "--branching" => {
+                branching = match args_it.next().as_deref() {
+                    Some("forbid") => Branching::Forbid,
+                    Some("force") => Branching::ForceForbid,
+                    _ => return usage(),
+                }
+            }
+            "--search" => {
+                search_mode = match args_it.next().as_deref() {
+                    Some("dfs") => SearchMode::Dfs,
+                    Some("bfs") => SearchMode::Bfs,
+                    Some("best") => SearchMode::BestFirst,
+                    Some("dfs-best") => SearchMode::DfsThenBestFirst,
+                    _ => return usage(),
+                }
+            }
+            "--special" => {
+                special_node = match args_it.next() {
+                    Some(value) if value == "max" => SpecialNode::MaxBound,
+                    Some(value) if value.starts_with("try:") => {
+                        match parse_value(Some(value[4..].to_string())) {
+                            Some(count) if count > 0 => SpecialNode::Try(count),
+                            _ => return usage(),
+                        }
+                    }
+                    value => match parse_value(value) {
+                        Some(node) => SpecialNode::Fixed(node),
+                        None => return usage(),
+                    },
+                }
+            }
+            "--step-formula" => {
+                config.step_formula = match args_it.next().as_deref() {
+                    Some("polyak") => StepFormula::Polyak,
+                    Some("hwc") => StepFormula::HeldWolfeCrowder,
+                    Some("vj") => StepFormula::VolgenantJonker,
+                    _ => return usage(),
+                }
+            }
+            "--initial-step" => match parse_value(args_it.next()) {
+                Some(step) if step > 0.0 && f64::is_finite(step) => config.initial_step = step,
+                _ => return usage(),
+            },
+            "--patience" => match parse_value(args_it.next()) {
+                Some(patience) if patience > 0 => {
+                    config.patience = patience;
+                    halving_options = true;
+                }
+                _ => return usage(),
+            },
+            "--min-step" => match parse_value(args_it.next()) {
+                Some(step) if step > 0.0 && f64::is_finite(step) => {
+                    config.min_step = step;
+                    halving_options = true;
+                }
+                _ => return usage(),
+            },
+            "--max-iter" => match parse_value(args_it.next()) {
+                Some(max_iter) if max_iter > 0 => config.max_iter = Some(max_iter),
+                _ => return usage(),
+            },
+            "--smoothing" => match parse_value(args_it.next()) {
+                Some(alpha) if (0.0..1.0).contains(&alpha) => config.smoothing = alpha,
+                _ => return usage(),
+            },
+            "--child-initial-step" => match parse_value(args_it.next()) {
+                Some(step) if step > 0.0 && f64::is_finite(step) => {
+                    child_config.initial_step = step
+                }
+                _ => return usage(),
+            },
+            "--child-patience" => match parse_value(args_it.next()) {
+                Some(patience) if patience > 0 => {
+                    child_config.patience = patience;
+                    halving_options = true;
+                }
+                _ => return usage(),
+            },
+            "--child-min-step" => match parse_value(args_it.next()) {
+                Some(step) if step > 0.0 && f64::is_finite(step) => {
+                    child_config.min_step = step;
+                    halving_options = true;
+                }
+                _ => return usage(),
+            },
+            "--child-max-iter" => match parse_value(args_it.next()) {
+                Some(max_iter) if max_iter > 0 => child_config.max_iter = Some(max_iter),
+                _ => return usage(),
+            },
+            _ => args.push(arg),
+        }
+    }
+
+    if args.len() < 2 || args.len() > 3 {
+        return usage();
+    }
+    if config.initial_step <= config.min_step || child_config.initial_step <= child_config.min_step
+    {
+        eprintln!("The initial step must be larger than the minimum step");
+        return;
+    }
+    if halving_options && matches!(config.step_formula, StepFormula::VolgenantJonker) {
         eprintln!(
-            "Use cargo run /path/to/instance UPPERBOUND or ./path/to/bin /path/to/instance UPPERBOUND"
+            "The Volgenant-Jonker step doesn't use --patience or --min-step, its length is set with --max-iter"
         );
         return;
     }
 
     let instance = instance_reader::read_data(&args[1]);
-    let upperbound = args[2].parse().unwrap();
+    if matches!(special_node, SpecialNode::Fixed(node) if node >= instance.dimension) {
+        eprintln!(
+            "The special node must be smaller than the dimension {}",
+            instance.dimension
+        );
+        return;
+    }
+
+    let (upperbound, incumbent) = match args.get(2) {
+        Some(upperbound) => (upperbound.parse().unwrap(), None),
+        None => {
+            let s = upperbound_tour(&instance);
+            println!("Heuristic upperbound: {}", s.value);
+            (s.value, Some(s))
+        }
+    };
 
-    let s = bnb_lr(&instance, upperbound);
+    let graph = match graph_type.as_str() {
+        "complete" => Graph::Complete(mst_algorithm),
+        "nearest" => Graph::Sparse(CandidateGraph::from(
+            instance.nearest_neighbours(candidates),
+            &instance,
+        )),
+        "quadrant" => {
+            let Some(neighbours) = instance.quadrant_neighbours(candidates) else {
+                eprintln!("Quadrant neighbours need an instance with node coordinates");
+                return;
+            };
+            Graph::Sparse(CandidateGraph::from(neighbours, &instance))
+        }
+        _ => return usage(),
+    };
 
-    if s.is_none() {
-        println!("No better solution was found for this upperbound");
+    if only_lowerbound {
+        let root = solve_root(&instance, upperbound as f64, &graph, &config, special_node);
+        // The subgradient stops as soon as the bound reaches the upperbound,
+        // so its value is not the Held-Karp bound then
+        let stopped = root.solution.is_none() && !can_improve(root.value, upperbound as f64);
+        if stopped && incumbent.is_none() {
+            println!(
+                "No tour with cost below {upperbound}, the bound reached it before converging"
+            );
+        } else {
+            println!("Lower bound: {:.2}", root.value);
+        }
+        println!("Special node: {}", root.special_node);
+        if root.solution.is_some() {
+            println!("The 1-tree is a tour, the lower bound is optimal");
+        } else if stopped && incumbent.is_some() {
+            println!("The bound reached the heuristic upperbound, the heuristic tour is optimal");
+        }
         return;
     }
 
-    let s = s.unwrap();
+    let s = match bnb_lr(
+        &instance,
+        upperbound,
+        incumbent,
+        &BnbConfig {
+            graph,
+            subgradient: config,
+            child_subgradient: SubgradientConfig {
+                step_formula: config.step_formula,
+                smoothing: config.smoothing,
+                ..child_config
+            },
+            fixing,
+            branching,
+            search_mode,
+            special_node,
+        },
+    ) {
+        BnbResult::Improved(s) => s,
+        BnbResult::NoTourBelow {
+            incumbent: Some(s),
+            lowerbound,
+        } => {
+            println!("No better solution exists, the heuristic tour is optimal");
+            println!("Lower bound: {lowerbound:.2}");
+            s
+        }
+        BnbResult::NoTourBelow {
+            incumbent: None,
+            lowerbound,
+        } => {
+            println!("No tour with cost below {upperbound}");
+            println!("Lower bound: {lowerbound:.2}");
+            return;
+        }
+    };
 
     println!("Solution:");
     for node in s.sequence.iter().take(instance.dimension) {
@@ -0,0 +1,150 @@
+use instance_reader::Instance;
+
+use crate::ils::subsequence::Subsequence;
+use crate::solution::Solution;
+
+// Depth first search over the prefixes of the sequence, starting at the depot
+struct Search<'a> {
+    instance: &'a Instance,
+    best: Solution,
+    prefix: Vec<usize>,
+    visited: Vec<bool>,
+    nodes: u64,
+}
+
+impl Search<'_> {
+    // Smallest latency that the arrivals after the prefix can add
+    // Each remaining node is reached by an edge at least as expensive as its cheapest
+    // incoming edge, and the edges are weighted by how many arrivals they delay,
+    // so pairing the cheapest edges with the largest weights gives a bound
+    fn bound(&self, sigma: &Subsequence, remaining: &[usize]) -> u32 {
+        let instance = self.instance;
+        let last = *self.prefix.last().unwrap();
+        let r = remaining.len() as u32;
+
+        let mut incoming = remaining
+            .iter()
+            .map(|&v| {
+                remaining
+                    .iter()
+                    .filter(|&&u| u != v)
+                    .map(|&u| instance.distance(u, v))
+                    .fold(instance.distance(last, v), u32::min)
+            })
+            .collect::<Vec<u32>>();
+        incoming.sort_unstable();
+
+        // The return to the depot is always the last edge
+        let back = remaining
+            .iter()
+            .map(|&u| instance.distance(u, 0))
+            .min()
+            .unwrap_or(instance.distance(last, 0));
+
+        // Every remaining arrival, the return included, happens after the prefix
+        let weighted = incoming
+            .iter()
+            .zip((2..=r + 1).rev())
+            .map(|(cost, weight)| cost * weight)
+            .sum::<u32>();
+
+        (r + 1) * sigma.t + weighted + back
+    }
+
+    fn branch(&mut self, sigma: &Subsequence) {
+        self.nodes += 1;
+        let instance = self.instance;
+        let last = *self.prefix.last().unwrap();
+
+        let mut remaining = (1..instance.dimension)
+            .filter(|&v| !self.visited[v])
+            .collect::<Vec<usize>>();
+
+        if remaining.is_empty() {
+            let tour = sigma.concatenate(&Subsequence::from_node(0, false), instance);
+            if tour.c < self.best.value {
+                self.best.sequence = self.prefix.iter().copied().chain([0]).collect();
+                self.best.value = tour.c;
+            }
+            return;
+        }
+
+        if sigma.c + self.bound(sigma, &remaining) >= self.best.value {
+            return;
+        }
+
+        // Closest nodes first so good solutions are found early
+        remaining.sort_unstable_by_key(|&v| instance.distance(last, v));
+
+        for v in remaining {
+            let child = sigma.concatenate(&Subsequence::from_node(v, false), instance);
+
+            self.visited[v] = true;
+            self.prefix.push(v);
+            self.branch(&child);
+            self.prefix.pop();
+            self.visited[v] = false;
+        }
+    }
+}
+
+// Exact branch and bound for the minimum latency problem
+// The incumbent is used to prune from the start, it is returned if nothing better exists
+// Returns the optimal solution and how many nodes were visited
+pub fn bnb(instance: &Instance, incumbent: Solution) -> (Solution, u64) {
+    if instance.dimension < 2 {
+        return (incumbent, 0);
+    }
+
+    let mut visited = vec![false; instance.dimension];
+    visited[0] = true;
+
+    let mut search = Search {
+        instance,
+        best: incumbent,
+        prefix: vec![0],
+        visited,
+        nodes: 0,
+    };
+    search.branch(&Subsequence::from_node(0, true));
+
+    (search.best, search.nodes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::exact::held_karp;
+    use crate::ils::{IlsConfig, ils};
+    use held_karp::{Objective, cost};
+    use rand::SeedableRng;
+    use rand::rngs::StdRng;
+
+    // Same as --bnb, the search starts from the ILS solution
+    #[test]
+    fn bnb_after_ils_matches_held_karp() {
+        for name in ["burma14", "ulysses16", "gr17"] {
+            let instance = instance_reader::read_data(&format!("instances/{name}.tsp"));
+            let optimum = held_karp(&instance).unwrap();
+            let config = IlsConfig::new(2, instance.dimension as u32);
+            let incumbent = ils(&config, &instance, &mut StdRng::seed_from_u64(0));
+
+            let (s, _) = bnb(&instance, incumbent);
+            assert_eq!(cost(&instance, Objective::Latency, &s.sequence), s.value);
+            assert_eq!(s.value, optimum.value, "{name}");
+        }
+    }
+
+    // A poor incumbent, so the search has to find the optimum by itself
+    #[test]
+    fn bnb_from_poor_incumbent_matches_held_karp() {
+        let instance = instance_reader::read_data("instances/burma14.tsp");
+        let optimum = held_karp(&instance).unwrap();
+
+        let sequence: Vec<usize> = (0..instance.dimension).chain([0]).collect();
+        let value = cost(&instance, Objective::Latency, &sequence);
+        let (s, _) = bnb(&instance, Solution { sequence, value });
+        assert_eq!(cost(&instance, Objective::Latency, &s.sequence), s.value);
+        assert_eq!(s.value, optimum.value);
+    }
+}
@@ -0,0 +1,71 @@
+// Hungarian algorithm with potentials over a square n x n matrix, O(n³)
+// Returns the column assigned to each row
+pub fn solve(cost: &[f64], n: usize) -> Vec<usize> {
+    // Row and column potentials, everything is 1-indexed and 0 is a dummy column
+    let mut u = vec![0.0; n + 1];
+    let mut v = vec![0.0; n + 1];
+    // Row assigned to each column
+    let mut p = vec![0; n + 1];
+    let mut way = vec![0; n + 1];
+
+    for i in 1..=n {
+        p[0] = i;
+        let mut j0 = 0;
+        let mut minv = vec![f64::INFINITY; n + 1];
+        let mut used = vec![false; n + 1];
+
+        loop {
+            used[j0] = true;
+            let i0 = p[j0];
+            let mut delta = f64::INFINITY;
+            let mut j1 = 0;
+
+            for j in 1..=n {
+                if used[j] {
+                    continue;
+                }
+
+                let current = cost[(i0 - 1) * n + j - 1] - u[i0] - v[j];
+                if current < minv[j] {
+                    minv[j] = current;
+                    way[j] = j0;
+                }
+                if minv[j] < delta {
+                    delta = minv[j];
+                    j1 = j;
+                }
+            }
+
+            for j in 0..=n {
+                if used[j] {
+                    u[p[j]] += delta;
+                    v[j] -= delta;
+                } else {
+                    minv[j] -= delta;
+                }
+            }
+
+            j0 = j1;
+            if p[j0] == 0 {
+                break;
+            }
+        }
+
+        // Augmenting path
+        loop {
+            let j1 = way[j0];
+            p[j0] = p[j1];
+            j0 = j1;
+            if j0 == 0 {
+                break;
+            }
+        }
+    }
+
+    let mut assignment = vec![0; n];
+    for j in 1..=n {
+        assignment[p[j] - 1] = j - 1;
+    }
+
+    assignment
+}
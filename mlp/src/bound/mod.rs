@@ -0,0 +1,107 @@
+mod assignment;
+
+use instance_reader::Instance;
+
+const MAX_ITER: u32 = 100;
+// Iterations without improvement before halving the step
+const PATIENCE: u32 = 10;
+const MIN_STEP: f64 = 1e-3;
+
+// Cheapest way to reach customer j at position k (0-indexed) of the sequence
+// The edge that reaches position k is counted on every arrival after it, so it weighs n - k
+// The first position can only be reached from the depot, the others from any other customer
+fn predecessor(instance: &Instance, multipliers: &[f64], j: usize, k: usize) -> (f64, usize) {
+    let w = (instance.dimension - k) as f64;
+    if k == 0 {
+        return (w * instance.distance(0, j) as f64, 0);
+    }
+
+    (1..instance.dimension)
+        .filter(|&i| i != j)
+        .map(|i| (w * instance.distance(i, j) as f64 + multipliers[i], i))
+        .min_by(|a, b| a.0.total_cmp(&b.0))
+        .unwrap()
+}
+
+// Solves the relaxation for fixed multipliers, returns its value and subgradient
+fn relaxation(instance: &Instance, multipliers: &[f64]) -> (f64, Vec<f64>) {
+    let n = instance.dimension;
+    let m = n - 1;
+
+    let mut cost = Vec::with_capacity(m * m);
+    for j in 1..n {
+        for k in 0..m {
+            cost.push(predecessor(instance, multipliers, j, k).0);
+        }
+    }
+
+    // Every node is left once, so the multipliers are subtracted once
+    let mut value = -multipliers.iter().sum::<f64>();
+    let mut subgradient = vec![-1.0; n];
+    subgradient[0] = 0.0;
+
+    for (row, k) in assignment::solve(&cost, m).into_iter().enumerate() {
+        let (edge_cost, i) = predecessor(instance, multipliers, row + 1, k);
+        value += edge_cost;
+        if i != 0 {
+            subgradient[i] += 1.0;
+        }
+    }
+
+    // The return to the depot is the last arrival
+    let (edge_cost, i) = (1..n)
+        .map(|i| (instance.distance(i, 0) as f64 + multipliers[i], i))
+        .min_by(|a, b| a.0.total_cmp(&b.0))
+        .unwrap();
+    value += edge_cost;
+    subgradient[i] += 1.0;
+
+    (value, subgradient)
+}
+
+// Lagrangian bound over the position of each customer in the sequence
+// Relaxing that each customer is left exactly once, every customer is reached by its cheapest
+// predecessor for its position and the positions are chosen solving an assignment problem
+// The multipliers of the relaxed constraints are adjusted with a subgradient method,
+// which costs O(n³) per iteration
+pub fn lowerbound(instance: &Instance, upperbound: u32) -> u32 {
+    let n = instance.dimension;
+    if n < 2 {
+        return 0;
+    }
+
+    let upperbound = upperbound as f64;
+    // The depot multiplier stays at 0, its edge is fixed on the first position
+    let mut multipliers = vec![0.0; n];
+    let mut best = f64::NEG_INFINITY;
+    let mut eps = 1.0;
+    let mut iter_not_improved = 0;
+
+    for _ in 0..MAX_ITER {
+        let (value, subgradient) = relaxation(instance, &multipliers);
+
+        if value > best {
+            best = value;
+            iter_not_improved = 0;
+        } else {
+            iter_not_improved += 1;
+            if iter_not_improved >= PATIENCE {
+                eps /= 2.0;
+                iter_not_improved = 0;
+            }
+        }
+
+        let norm = subgradient.iter().map(|g| g * g).sum::<f64>();
+        if norm == 0.0 || best > upperbound - 1.0 || eps < MIN_STEP {
+            break;
+        }
+
+        let step = eps * (upperbound - value) / norm;
+        for (multiplier, g) in multipliers.iter_mut().zip(&subgradient) {
+            *multiplier += step * g;
+        }
+    }
+
+    // Solutions have integer cost, the tolerance covers rounding errors of the multipliers
+    (best - 1e-6).ceil().max(0.0) as u32
+}
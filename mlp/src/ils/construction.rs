@@ -1,6 +1,6 @@
 use crate::solution::*;
 use instance_reader::Instance;
-use rand::{Rng, rng};
+use rand::Rng;
 
 use super::subsequence::{SubsequenceMatrix, update_solution};
 
@@ -26,7 +26,13 @@ fn calculate_insertion_cost(s: &Solution, cl: &[usize], instance: &Instance) ->
 }
 
 // Constructs a solution with a grasp algorithm using best insertion
-pub fn construction(subseq_matrix: &mut SubsequenceMatrix, instance: &Instance) -> Solution {
+// alpha is drawn at every step when it isn't given
+pub fn construction(
+    subseq_matrix: &mut SubsequenceMatrix,
+    instance: &Instance,
+    alpha: Option<f64>,
+    rng: &mut impl Rng,
+) -> Solution {
     // cl is the candidate list to insert into the solution
     let mut cl: Vec<usize> = (1..instance.dimension).collect();
     let mut s = Solution {
@@ -38,10 +44,10 @@ pub fn construction(subseq_matrix: &mut SubsequenceMatrix, instance: &Instance)
         let mut insertion_cost = calculate_insertion_cost(&s, &cl, instance);
         insertion_cost.sort_unstable_by_key(|x| x.value);
 
-        // Choose a random index from insertion cost but the first values have more priority
-        let alpha: f64 = rng().random_range(1e-10..1.0);
-        let chosen =
-            (rng().random::<u32>() % (alpha * insertion_cost.len() as f64).ceil() as u32) as usize;
+        // Choose uniformly among the best alpha fraction of the insertions, at least one
+        let alpha = alpha.unwrap_or_else(|| rng.random_range(1e-10..1.0));
+        let rcl_size = ((alpha * insertion_cost.len() as f64).ceil() as usize).max(1);
+        let chosen = rng.random_range(0..rcl_size);
 
         let chosen_insertion = &insertion_cost[chosen];
 
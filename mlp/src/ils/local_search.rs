@@ -1,23 +1,75 @@
-use rand::{Rng, rng};
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::{Rng, SeedableRng};
+use std::ops::Range;
 
 use crate::solution::Solution;
 use instance_reader::Instance;
 
 use super::subsequence::{Subsequence, SubsequenceMatrix, update_solution};
 
-#[derive(Clone)]
+#[derive(Debug, Clone)]
 enum Searches {
     Swap,
     TwoOpt,
     OrOpt(usize),
+    // Or-opt that inserts the block in the opposite direction
+    ReversedOrOpt(usize),
+    SegmentInsertion(SegmentInsertion),
 }
 
-fn best_swap(s: &mut Solution, subseq_matrix: &mut SubsequenceMatrix, instance: &Instance) -> bool {
+// Or-opt with blocks of any size up to max_size, also reinserted reversed when reversed is set
+#[derive(Debug, Clone, Copy)]
+pub struct SegmentInsertion {
+    pub max_size: usize,
+    pub reversed: bool,
+}
+
+// Best improvement applies the best move of a neighbourhood,
+// first improvement applies the first improving move found in a random order
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub enum Improvement {
+    #[default]
+    Best,
+    First,
+}
+
+#[derive(Clone, Copy)]
+struct Scan {
+    first: bool,
+    seed: u64,
+}
+
+impl Scan {
+    // The generator is only used in first improvement
+    fn new(improvement: Improvement, rng: &mut impl Rng) -> Self {
+        let first = improvement == Improvement::First;
+        let seed = if first { rng.random() } else { 0 };
+        Scan { first, seed }
+    }
+
+    // Positions of the range in order, shuffled in first improvement
+    // A scan always gives the same order, a new one is drawn for every neighbourhood
+    fn positions(self, range: Range<usize>) -> Vec<usize> {
+        let mut positions: Vec<usize> = range.collect();
+        if self.first {
+            positions.shuffle(&mut StdRng::seed_from_u64(self.seed));
+        }
+        positions
+    }
+}
+
+fn best_swap(
+    s: &mut Solution,
+    subseq_matrix: &mut SubsequenceMatrix,
+    scan: Scan,
+    instance: &Instance,
+) -> bool {
     let mut best_delta = 0;
     let mut best_i = usize::MAX;
     let mut best_j = usize::MAX;
 
-    for i in 1..s.sequence.len() - 2 {
+    'scan: for i in scan.positions(1..s.sequence.len() - 2) {
         for j in i + 2..s.sequence.len() - 1 {
             let sigma = subseq_matrix
                 .get(0, i - 1)
@@ -35,6 +87,9 @@ fn best_swap(s: &mut Solution, subseq_matrix: &mut SubsequenceMatrix, instance:
                 best_delta = delta;
                 best_i = i;
                 best_j = j;
+                if scan.first {
+                    break 'scan;
+                }
             }
         }
     }
@@ -49,12 +104,17 @@ fn best_swap(s: &mut Solution, subseq_matrix: &mut SubsequenceMatrix, instance:
     false
 }
 
-fn best_2opt(s: &mut Solution, subseq_matrix: &mut SubsequenceMatrix, instance: &Instance) -> bool {
+fn best_2opt(
+    s: &mut Solution,
+    subseq_matrix: &mut SubsequenceMatrix,
+    scan: Scan,
+    instance: &Instance,
+) -> bool {
     let mut best_delta = 0;
     let mut best_i = usize::MAX;
     let mut best_j = usize::MAX;
 
-    for i in 1..s.sequence.len() - 2 {
+    'scan: for i in scan.positions(1..s.sequence.len() - 2) {
         for j in i + 1..s.sequence.len() - 1 {
             let sigma = subseq_matrix
                 .get(0, i - 1)
@@ -70,6 +130,9 @@ fn best_2opt(s: &mut Solution, subseq_matrix: &mut SubsequenceMatrix, instance:
                 best_delta = delta;
                 best_i = i;
                 best_j = j;
+                if scan.first {
+                    break 'scan;
+                }
             }
         }
     }
@@ -84,17 +147,28 @@ fn best_2opt(s: &mut Solution, subseq_matrix: &mut SubsequenceMatrix, instance:
     false
 }
 
-fn best_oropt(
-    s: &mut Solution,
-    subseq_matrix: &mut SubsequenceMatrix,
+// Best or-opt move for one block size as (delta, block start, insertion position)
+// The delta is 0 when no move improves the solution
+fn find_oropt(
+    s: &Solution,
+    subseq_matrix: &SubsequenceMatrix,
+    scan: Scan,
     block_size: usize,
+    reversed: bool,
     instance: &Instance,
-) -> bool {
+) -> (i32, usize, usize) {
     let mut best_delta = 0;
     let mut best_i = usize::MAX;
     let mut best_j = usize::MAX;
 
-    for i in 1..s.sequence.len() - block_size {
+    'scan: for i in scan.positions(1..s.sequence.len() - block_size) {
+        // The matrix keeps the reversed subsequences below the diagonal
+        let block = if reversed {
+            subseq_matrix.get(i + block_size - 1, i)
+        } else {
+            subseq_matrix.get(i, i + block_size - 1)
+        };
+
         let mut check_delta = |sigma: Subsequence, j: usize| {
             let delta = sigma.c as i32 - s.value as i32;
 
@@ -102,21 +176,25 @@ fn best_oropt(
                 best_delta = delta;
                 best_i = i;
                 best_j = j;
+                return true;
             }
+            false
         };
 
         // Insert block before i
         for j in 1..i.saturating_sub(2) {
             let sigma = subseq_matrix
                 .get(0, j)
-                .concatenate(subseq_matrix.get(i, i + block_size - 1), instance)
+                .concatenate(block, instance)
                 .concatenate(subseq_matrix.get(j + 1, i - 1), instance)
                 .concatenate(
                     subseq_matrix.get(i + block_size, subseq_matrix.dimension() - 1),
                     instance,
                 );
 
-            check_delta(sigma, j);
+            if check_delta(sigma, j) && scan.first {
+                break 'scan;
+            }
         }
 
         // Insert block after i
@@ -124,56 +202,333 @@ fn best_oropt(
             let sigma = subseq_matrix
                 .get(0, i - 1)
                 .concatenate(subseq_matrix.get(i + block_size, j), instance)
-                .concatenate(subseq_matrix.get(i, i + block_size - 1), instance)
+                .concatenate(block, instance)
                 .concatenate(
                     subseq_matrix.get(j + 1, subseq_matrix.dimension() - 1),
                     instance,
                 );
 
-            check_delta(sigma, j);
+            if check_delta(sigma, j) && scan.first {
+                break 'scan;
+            }
         }
     }
 
-    if best_delta < 0 {
-        if best_i < best_j {
-            s.sequence[best_i..=best_j].rotate_left(block_size);
-            update_solution(s, subseq_matrix, instance, Some((best_i, best_j)));
-        } else {
-            s.sequence[(best_j + 1)..(best_i + block_size)].rotate_right(block_size);
-            update_solution(
-                s,
-                subseq_matrix,
-                instance,
-                Some((best_j, best_i + block_size)),
-            );
+    (best_delta, best_i, best_j)
+}
+
+fn apply_oropt(
+    s: &mut Solution,
+    subseq_matrix: &mut SubsequenceMatrix,
+    (best_i, best_j): (usize, usize),
+    block_size: usize,
+    reversed: bool,
+    instance: &Instance,
+) {
+    if best_i < best_j {
+        s.sequence[best_i..=best_j].rotate_left(block_size);
+        if reversed {
+            s.sequence[best_j + 1 - block_size..=best_j].reverse();
+        }
+        update_solution(s, subseq_matrix, instance, Some((best_i, best_j)));
+    } else {
+        s.sequence[(best_j + 1)..(best_i + block_size)].rotate_right(block_size);
+        if reversed {
+            s.sequence[best_j + 1..=best_j + block_size].reverse();
         }
+        update_solution(
+            s,
+            subseq_matrix,
+            instance,
+            Some((best_j, best_i + block_size)),
+        );
+    }
+}
 
+fn best_oropt(
+    s: &mut Solution,
+    subseq_matrix: &mut SubsequenceMatrix,
+    scan: Scan,
+    block_size: usize,
+    reversed: bool,
+    instance: &Instance,
+) -> bool {
+    let (delta, i, j) = find_oropt(s, subseq_matrix, scan, block_size, reversed, instance);
+    if delta < 0 {
+        apply_oropt(s, subseq_matrix, (i, j), block_size, reversed, instance);
         return true;
     }
 
     false
 }
 
-pub fn local_search(s: &mut Solution, subseq_matrix: &mut SubsequenceMatrix, instance: &Instance) {
+// Or-opt over every block size up to the maximum
+// Each move is still evaluated in O(1) by concatenating subsequences of the matrix
+// First improvement stops at the first block size with an improving move
+fn best_segment_insertion(
+    s: &mut Solution,
+    subseq_matrix: &mut SubsequenceMatrix,
+    scan: Scan,
+    config: SegmentInsertion,
+    instance: &Instance,
+) -> bool {
+    let max_size = config.max_size.min(s.sequence.len() - 2);
+    let orientations: &[bool] = if config.reversed {
+        &[false, true]
+    } else {
+        &[false]
+    };
+
+    let mut moves = (1..=max_size)
+        .flat_map(|block_size| {
+            orientations
+                .iter()
+                .map(move |&reversed| (block_size, reversed))
+        })
+        .map(|(block_size, reversed)| {
+            let best = find_oropt(s, subseq_matrix, scan, block_size, reversed, instance);
+            (best, block_size, reversed)
+        });
+    let best = if scan.first {
+        moves.find(|(best, _, _)| best.0 < 0)
+    } else {
+        moves.min_by_key(|(best, _, _)| best.0)
+    };
+
+    if let Some(((delta, i, j), block_size, reversed)) = best
+        && delta < 0
+    {
+        apply_oropt(s, subseq_matrix, (i, j), block_size, reversed, instance);
+        return true;
+    }
+
+    false
+}
+
+// Applies the move chosen by the neighbourhood, returns whether it improved the solution
+fn improve(
+    search_type: &Searches,
+    s: &mut Solution,
+    subseq_matrix: &mut SubsequenceMatrix,
+    scan: Scan,
+    instance: &Instance,
+) -> bool {
     use Searches::*;
-    const SEARCHES: [Searches; 5] = [Swap, TwoOpt, OrOpt(1), OrOpt(2), OrOpt(3)];
+    match search_type {
+        Swap => best_swap(s, subseq_matrix, scan, instance),
+        TwoOpt => best_2opt(s, subseq_matrix, scan, instance),
+        OrOpt(block_size) => best_oropt(s, subseq_matrix, scan, *block_size, false, instance),
+        ReversedOrOpt(block_size) => {
+            best_oropt(s, subseq_matrix, scan, *block_size, true, instance)
+        }
+        SegmentInsertion(config) => {
+            best_segment_insertion(s, subseq_matrix, scan, *config, instance)
+        }
+    }
+}
 
-    let mut nl = SEARCHES.to_vec();
+pub fn local_search(
+    s: &mut Solution,
+    subseq_matrix: &mut SubsequenceMatrix,
+    instance: &Instance,
+    segment_insertion: Option<SegmentInsertion>,
+    improvement: Improvement,
+    rng: &mut impl Rng,
+) {
+    use Searches::*;
+    const SEARCHES: [Searches; 7] = [
+        Swap,
+        TwoOpt,
+        OrOpt(1),
+        OrOpt(2),
+        OrOpt(3),
+        ReversedOrOpt(2),
+        ReversedOrOpt(3),
+    ];
+
+    let searches: Vec<Searches> = SEARCHES
+        .into_iter()
+        .chain(segment_insertion.map(SegmentInsertion))
+        .collect();
+    let mut nl = searches.clone();
 
     while !nl.is_empty() {
-        let chosen = rng().random_range(0..nl.len());
+        let chosen = rng.random_range(0..nl.len());
         let search_type = &nl[chosen];
+        let scan = Scan::new(improvement, rng);
 
-        let improved = match search_type {
-            Swap => best_swap(s, subseq_matrix, instance),
-            TwoOpt => best_2opt(s, subseq_matrix, instance),
-            OrOpt(block_size) => best_oropt(s, subseq_matrix, *block_size, instance),
-        };
+        let improved = improve(search_type, s, subseq_matrix, scan, instance);
 
         if improved {
-            nl = SEARCHES.to_vec();
+            nl = searches.clone();
         } else {
             nl.swap_remove(chosen);
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use held_karp::{Objective, cost};
+
+    fn random_solution(
+        instance: &Instance,
+        subseq_matrix: &mut SubsequenceMatrix,
+        rng: &mut StdRng,
+    ) -> Solution {
+        let mut sequence: Vec<usize> = (1..instance.dimension).collect();
+        sequence.shuffle(rng);
+        sequence.insert(0, 0);
+        sequence.push(0);
+
+        let mut s = Solution { sequence, value: 0 };
+        update_solution(&mut s, subseq_matrix, instance, None);
+        s
+    }
+
+    fn assert_valid(s: &Solution, instance: &Instance) {
+        let n = instance.dimension;
+        assert_eq!((s.sequence[0], s.sequence[n]), (0, 0));
+        let mut nodes = s.sequence[..n].to_vec();
+        nodes.sort_unstable();
+        assert!(nodes.into_iter().eq(0..n), "not a permutation");
+        assert_eq!(cost(instance, Objective::Latency, &s.sequence), s.value);
+    }
+
+    // A random solution always has an improving move in every neighbourhood
+    #[test]
+    fn first_improvement_applies_improving_moves() {
+        use Searches::*;
+        let instance = instance_reader::read_data("instances/kroA100.tsp");
+        let mut subseq_matrix = SubsequenceMatrix::from(instance.dimension + 1);
+        let mut rng = StdRng::seed_from_u64(0);
+        let config = super::SegmentInsertion {
+            max_size: 6,
+            reversed: true,
+        };
+
+        for _ in 0..10 {
+            for search_type in [
+                Swap,
+                TwoOpt,
+                OrOpt(1),
+                OrOpt(2),
+                OrOpt(3),
+                ReversedOrOpt(2),
+                ReversedOrOpt(3),
+                SegmentInsertion(config),
+            ] {
+                let mut s = random_solution(&instance, &mut subseq_matrix, &mut rng);
+                let value = s.value;
+                let scan = Scan::new(Improvement::First, &mut rng);
+
+                assert!(
+                    improve(&search_type, &mut s, &mut subseq_matrix, scan, &instance),
+                    "{search_type:?}"
+                );
+                assert!(s.value < value, "{search_type:?}");
+                assert_valid(&s, &instance);
+            }
+        }
+    }
+
+    // Applies or-opt moves until none improves, every delta must match the new value
+    fn descend_oropt(
+        s: &mut Solution,
+        subseq_matrix: &mut SubsequenceMatrix,
+        block_size: usize,
+        reversed: bool,
+        improvement: Improvement,
+        rng: &mut StdRng,
+        instance: &Instance,
+    ) {
+        loop {
+            let scan = Scan::new(improvement, rng);
+            let (delta, i, j) = find_oropt(s, subseq_matrix, scan, block_size, reversed, instance);
+            if delta == 0 {
+                return;
+            }
+
+            let value = s.value as i32;
+            apply_oropt(s, subseq_matrix, (i, j), block_size, reversed, instance);
+            assert_valid(s, instance);
+            assert_eq!(s.value as i32, value + delta, "block of {block_size}");
+        }
+    }
+
+    #[test]
+    fn reversed_oropt_matches_recalculate() {
+        let instance = instance_reader::read_data("instances/gr17.tsp");
+        let mut subseq_matrix = SubsequenceMatrix::from(instance.dimension + 1);
+        let mut rng = StdRng::seed_from_u64(0);
+
+        for improvement in [Improvement::Best, Improvement::First] {
+            for block_size in 1..=3 {
+                for _ in 0..10 {
+                    let mut s = random_solution(&instance, &mut subseq_matrix, &mut rng);
+                    descend_oropt(
+                        &mut s,
+                        &mut subseq_matrix,
+                        block_size,
+                        true,
+                        improvement,
+                        &mut rng,
+                        &instance,
+                    );
+                }
+            }
+        }
+    }
+
+    // Segment insertion picks among the block sizes, each of them is also checked on its own
+    #[test]
+    fn segment_insertion_matches_recalculate() {
+        let instance = instance_reader::read_data("instances/gr17.tsp");
+        let mut subseq_matrix = SubsequenceMatrix::from(instance.dimension + 1);
+        let mut rng = StdRng::seed_from_u64(0);
+
+        for improvement in [Improvement::Best, Improvement::First] {
+            for reversed in [false, true] {
+                let config = SegmentInsertion {
+                    max_size: 8,
+                    reversed,
+                };
+
+                for block_size in 1..=config.max_size {
+                    for _ in 0..10 {
+                        let mut s = random_solution(&instance, &mut subseq_matrix, &mut rng);
+                        descend_oropt(
+                            &mut s,
+                            &mut subseq_matrix,
+                            block_size,
+                            reversed,
+                            improvement,
+                            &mut rng,
+                            &instance,
+                        );
+                    }
+                }
+
+                for _ in 0..10 {
+                    let mut s = random_solution(&instance, &mut subseq_matrix, &mut rng);
+                    loop {
+                        let scan = Scan::new(improvement, &mut rng);
+                        let value = s.value;
+                        if !best_segment_insertion(
+                            &mut s,
+                            &mut subseq_matrix,
+                            scan,
+                            config,
+                            &instance,
+                        ) {
+                            break;
+                        }
+                        assert!(s.value < value);
+                        assert_valid(&s, &instance);
+                    }
+                }
+            }
+        }
+    }
+}
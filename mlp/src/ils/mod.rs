@@ -1,46 +1,180 @@
 mod construction;
 mod local_search;
 mod perturbation;
-mod subsequence;
+pub mod subsequence;
 
 use crate::solution::Solution;
 use construction::construction;
+pub use grasp::Alpha;
+use grasp::GraspAlpha;
 use instance_reader::Instance;
 use local_search::local_search;
+pub use local_search::{Improvement, SegmentInsertion};
 use perturbation::perturbation;
+use rand::Rng;
+use rand::SeedableRng;
+use rand::rngs::StdRng;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::thread;
+use std::time::{Duration, Instant};
 use subsequence::SubsequenceMatrix;
 
-pub fn ils(max_iter: u32, max_iter_ils: u32, instance: &Instance) -> Solution {
-    let mut best = Solution {
-        value: u32::MAX,
-        ..Default::default()
-    };
-    // let mut subseq_matrix =
-    //     vec![vec![Subsequence::default(); instance.dimension + 1]; instance.dimension + 1];
-    let mut subseq_matrix = SubsequenceMatrix::from(instance.dimension + 1);
+#[derive(Debug, Clone)]
+pub struct IlsConfig {
+    // Number of restarts
+    pub max_iter: u32,
+    // Perturbations without improvement before restarting
+    pub max_iter_ils: u32,
+    // The search stops between local searches once the limit or the target is reached
+    pub time_limit: Option<Duration>,
+    pub target: Option<u32>,
+    // Restarts run in parallel on this many threads
+    pub threads: usize,
+    // Adds or-opt with longer blocks to the local search
+    pub segment_insertion: Option<SegmentInsertion>,
+    // How the local search neighbourhoods are scanned
+    pub improvement: Improvement,
+    // Size of the restricted candidate list of the construction
+    pub alpha: Alpha,
+}
 
-    for _ in 0..max_iter {
-        let mut s = construction(&mut subseq_matrix, instance);
+impl IlsConfig {
+    pub fn new(max_iter: u32, max_iter_ils: u32) -> Self {
+        IlsConfig {
+            max_iter,
+            max_iter_ils,
+            time_limit: None,
+            target: None,
+            threads: 1,
+            segment_insertion: None,
+            improvement: Improvement::Best,
+            alpha: Alpha::Random,
+        }
+    }
+}
 
-        let mut inner_best = s.clone();
+// One restart: construction followed by perturbations until max_iter_ils fail to improve
+fn restart(
+    config: &IlsConfig,
+    subseq_matrix: &mut SubsequenceMatrix,
+    instance: &Instance,
+    alpha: Option<f64>,
+    rng: &mut impl Rng,
+    should_stop: impl Fn(&Solution) -> bool,
+) -> Solution {
+    let mut s = construction(subseq_matrix, instance, alpha, rng);
 
-        let mut iter_ils = 1;
-        while iter_ils <= max_iter_ils {
-            local_search(&mut s, &mut subseq_matrix, instance);
+    let mut inner_best = s.clone();
 
-            if s.value < inner_best.value {
-                inner_best = s.clone();
-                iter_ils = 0;
-            }
+    let mut iter_ils = 1;
+    while iter_ils <= config.max_iter_ils {
+        local_search(
+            &mut s,
+            subseq_matrix,
+            instance,
+            config.segment_insertion,
+            config.improvement,
+            rng,
+        );
 
-            s = perturbation(inner_best.clone(), &mut subseq_matrix, instance);
-            iter_ils += 1;
+        if s.value < inner_best.value {
+            inner_best = s.clone();
+            iter_ils = 0;
         }
 
-        if inner_best.value < best.value {
-            best = inner_best;
+        if should_stop(&inner_best) {
+            break;
         }
+
+        s = perturbation(inner_best.clone(), subseq_matrix, instance, rng);
+        iter_ils += 1;
     }
 
-    best
+    inner_best
+}
+
+// Restarts run on a pool of threads, each one with a generator seeded from rng
+// and every thread with its own subsequence matrix
+// The result only depends on rng unless the search is stopped by the time limit or the target
+pub fn ils(config: &IlsConfig, instance: &Instance, rng: &mut impl Rng) -> Solution {
+    let deadline = config.time_limit.map(|limit| Instant::now() + limit);
+    let stop = AtomicBool::new(false);
+    let should_stop = |best: &Solution| {
+        if deadline.is_some_and(|deadline| Instant::now() >= deadline)
+            || config.target.is_some_and(|target| best.value <= target)
+        {
+            stop.store(true, Ordering::Relaxed);
+        }
+        stop.load(Ordering::Relaxed)
+    };
+
+    // The first restart always runs, so there is a solution even when the search stops at once
+    let seeds: Vec<u64> = (0..config.max_iter.max(1)).map(|_| rng.random()).collect();
+    let grasp_alpha = GraspAlpha::new(&config.alpha, seeds.len());
+    let next_start = AtomicUsize::new(0);
+
+    let worker = || {
+        let mut subseq_matrix = SubsequenceMatrix::from(instance.dimension + 1);
+        let mut found = Vec::new();
+        loop {
+            let start = next_start.fetch_add(1, Ordering::Relaxed);
+            if start >= seeds.len() {
+                return found;
+            }
+            if stop.load(Ordering::Relaxed) {
+                grasp_alpha.skip(start);
+                return found;
+            }
+
+            let mut rng = StdRng::seed_from_u64(seeds[start]);
+            let (alpha, alpha_index) = grasp_alpha.choose(start, &mut rng);
+            let s = restart(
+                config,
+                &mut subseq_matrix,
+                instance,
+                alpha,
+                &mut rng,
+                should_stop,
+            );
+            grasp_alpha.report(start, alpha_index, s.value);
+            found.push((start, s));
+        }
+    };
+
+    let best = thread::scope(|scope| {
+        let workers: Vec<_> = (0..config.threads.max(1))
+            .map(|_| scope.spawn(worker))
+            .collect();
+
+        workers
+            .into_iter()
+            .flat_map(|worker| worker.join().unwrap())
+            .min_by_key(|(start, s)| (s.value, *start))
+    });
+
+    best.map(|(_, s)| s)
+        .expect("The first restart always finishes")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::exact::held_karp;
+    use held_karp::{Objective, cost};
+
+    #[test]
+    fn ils_finds_the_optimum() {
+        for name in ["burma14", "ulysses16", "gr17"] {
+            let instance = instance_reader::read_data(&format!("instances/{name}.tsp"));
+            let optimum = held_karp(&instance).unwrap();
+            let config = IlsConfig::new(10, instance.dimension as u32);
+
+            let s = ils(&config, &instance, &mut StdRng::seed_from_u64(0));
+            let mut nodes = s.sequence[..instance.dimension].to_vec();
+            nodes.sort_unstable();
+            assert!(nodes.into_iter().eq(0..instance.dimension), "{name}");
+            assert_eq!(cost(&instance, Objective::Latency, &s.sequence), s.value);
+            assert_eq!(s.value, optimum.value, "{name}");
+        }
+    }
 }
@@ -3,7 +3,7 @@ use instance_reader::Instance;
 
 #[derive(Default, Clone)]
 pub struct Subsequence {
-    t: u32,
+    pub t: u32,
     pub c: u32,
     w: u32,
     first: usize,
@@ -29,12 +29,17 @@ impl Subsequence {
     }
 
     pub fn create_single_node(i: usize, s: &Solution) -> Self {
+        Self::from_node(s.sequence[i], i == 0)
+    }
+
+    // The depot at the start of the sequence is not an arrival
+    pub fn from_node(node: usize, is_start: bool) -> Self {
         Self {
             t: 0,
             c: 0,
-            w: if i == 0 { 0 } else { 1 },
-            first: s.sequence[i],
-            last: s.sequence[i],
+            w: if is_start { 0 } else { 1 },
+            first: node,
+            last: node,
         }
     }
 }
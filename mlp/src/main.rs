@@ -1,27 +1,185 @@
+mod bnb;
+mod bound;
+mod exact;
 mod ils;
 mod solution;
 
-use ils::ils;
+use bnb::bnb;
+use bound::lowerbound;
+use exact::{held_karp, max_dimension};
+use ils::{Alpha, IlsConfig, Improvement, SegmentInsertion, ils};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use solution::Solution;
 use std::env;
+use std::thread;
+use std::time::Duration;
+
+fn print_solution(s: &Solution) {
+    println!("Solution: ");
+    for v in s.sequence.iter().take(s.sequence.len() - 1) {
+        print!("{v} -> ");
+    }
+    println!("{}", s.sequence.last().unwrap());
+
+    println!("Cost: {}", s.value);
+}
+
+fn usage() {
+    eprintln!("Not enough arguments, you need to pass the path to an instance");
+    eprintln!("Use cargo run /path/to/instance or ./path/to/bin /path/to/instance");
+    eprintln!("Options:");
+    eprintln!(
+        "  --exact     solve instances up to {} nodes to optimality",
+        max_dimension()
+    );
+    eprintln!("  --bnb       solve the instance with branch and bound after the ILS");
+    eprintln!("  --bound     print a lagrangian lower bound and the gap after the ILS, O(n³)");
+    eprintln!("              per iteration so it takes long on large instances");
+    eprintln!("  --seed N    seed of the random number generator, random by default");
+    eprintln!("ILS options:");
+    eprintln!("  --alpha A                 RCL size as a fraction of the candidates, random");
+    eprintln!("                            (default, drawn at every step), a value in [0, 1],");
+    eprintln!("                            set:A,B,... (one value per restart) or");
+    eprintln!("                            reactive:A,B,... (favours values with better results)");
+    eprintln!("  --max-iter N              number of restarts");
+    eprintln!("  --max-iter-ils N          perturbations without improvement before restarting");
+    eprintln!("  --time-limit S            stop after S seconds with the best solution found");
+    eprintln!("  --target VALUE            stop once a solution this good is found");
+    eprintln!("  --segment-insertion MAX   or-opt with blocks of up to MAX nodes");
+    eprintln!("  --reverse-segments        also reinsert those blocks reversed");
+    eprintln!("  --first-improvement       apply the first improving move of each neighbourhood");
+    eprintln!(
+        "  --threads N               restarts run in parallel, default is the number of cores"
+    );
+}
+
+fn parse_value<T: std::str::FromStr>(value: Option<String>) -> Option<T> {
+    value.and_then(|value| value.parse().ok())
+}
 
 fn main() {
-    let args: Vec<String> = env::args().collect();
+    let mut args = Vec::new();
+    // Solves small instances to optimality instead of running the ILS
+    let mut exact = false;
+    // Proves the ILS solution optimal or improves it with branch and bound
+    let mut branch_and_bound = false;
+    // The bound solves an assignment problem per iteration, so it's only computed on request
+    let mut bound = false;
+    let mut seed = None;
+    let mut max_iter = None;
+    let mut max_iter_ils = None;
+    let mut time_limit = None;
+    let mut target = None;
+    let mut threads = None;
+    let mut segment_max_size = None;
+    let mut reverse_segments = false;
+    let mut improvement = Improvement::Best;
+    let mut alpha = Alpha::Random;
+
+    let mut args_it = env::args();
+    while let Some(arg) = args_it.next() {
+        match arg.as_str() {
+            "--exact" => exact = true,
+            "--bnb" => branch_and_bound = true,
+            "--bound" => bound = true,
+            "--seed" => match parse_value(args_it.next()) {
+                Some(value) => seed = Some(value),
+                None => return usage(),
+            },
+            "--max-iter" => match parse_value(args_it.next()) {
+                Some(value) if value > 0 => max_iter = Some(value),
+                _ => return usage(),
+            },
+            "--max-iter-ils" => match parse_value(args_it.next()) {
+                Some(value) => max_iter_ils = Some(value),
+                None => return usage(),
+            },
+            "--time-limit" => match parse_value(args_it.next()) {
+                Some(seconds) if seconds >= 0.0 => {
+                    time_limit = Some(Duration::from_secs_f64(seconds))
+                }
+                _ => return usage(),
+            },
+            "--target" => match parse_value(args_it.next()) {
+                Some(value) => target = Some(value),
+                None => return usage(),
+            },
+            "--segment-insertion" => match parse_value(args_it.next()) {
+                Some(value) if value > 0 => segment_max_size = Some(value),
+                _ => return usage(),
+            },
+            "--reverse-segments" => reverse_segments = true,
+            "--first-improvement" => improvement = Improvement::First,
+            "--alpha" => match parse_value(args_it.next()) {
+                Some(value) => alpha = value,
+                None => return usage(),
+            },
+            "--threads" => match parse_value(args_it.next()) {
+                Some(value) if value > 0 => threads = Some(value),
+                _ => return usage(),
+            },
+            _ => args.push(arg),
+        }
+    }
 
     if args.len() != 2 {
-        eprintln!("Not enough arguments, you need to pass the path to an instance");
-        eprintln!("Use cargo run /path/to/instance or ./path/to/bin /path/to/instance");
-        return;
+        return usage();
     }
 
     let instance = instance_reader::read_data(&args[1]);
 
     println!("{}", instance.name);
-    let s = ils(10, std::cmp::min(100, instance.dimension) as u32, &instance);
-    println!("Solution: ");
-    for v in s.sequence.iter().take(s.sequence.len() - 1) {
-        print!("{v} -> ");
+    if exact {
+        let Some(s) = held_karp(&instance) else {
+            eprintln!(
+                "The exact solver only handles instances up to {} nodes",
+                max_dimension()
+            );
+            return;
+        };
+        print_solution(&s);
+        return;
     }
-    println!("{}", s.sequence.last().unwrap());
 
-    println!("Cost: {}", s.value);
+    // The seed is printed so any run can be repeated
+    let seed = seed.unwrap_or_else(|| rand::rng().random());
+    println!("Seed: {seed}");
+    let mut rng = StdRng::seed_from_u64(seed);
+
+    let config = IlsConfig {
+        time_limit,
+        target,
+        threads: threads
+            .unwrap_or_else(|| thread::available_parallelism().map_or(1, |threads| threads.get())),
+        segment_insertion: segment_max_size.map(|max_size| SegmentInsertion {
+            max_size,
+            reversed: reverse_segments,
+        }),
+        improvement,
+        alpha,
+        ..IlsConfig::new(
+            max_iter.unwrap_or(10),
+            max_iter_ils.unwrap_or(std::cmp::min(100, instance.dimension) as u32),
+        )
+    };
+
+    let s = ils(&config, &instance, &mut rng);
+    print_solution(&s);
+
+    if branch_and_bound {
+        let (s, nodes) = bnb(&instance, s);
+        println!("Branch and bound visited {nodes} nodes, optimal solution:");
+        print_solution(&s);
+        return;
+    }
+
+    if !bound {
+        return;
+    }
+
+    let lowerbound = lowerbound(&instance, s.value);
+    let gap = 100.0 * (s.value - lowerbound) as f64 / s.value as f64;
+    println!("Lower bound: {lowerbound}");
+    println!("Gap: {gap:.2}%");
 }
@@ -0,0 +1,13 @@
+use held_karp::Objective;
+use instance_reader::Instance;
+
+use crate::solution::Solution;
+
+pub use held_karp::max_dimension;
+
+// Optimal tour with the Held-Karp solver shared with mlp
+// Returns None when the instance doesn't fit in its memory limit
+pub fn held_karp(instance: &Instance) -> Option<Solution> {
+    held_karp::held_karp(instance, Objective::Tour)
+        .map(|(sequence, value)| Solution { sequence, value })
+}
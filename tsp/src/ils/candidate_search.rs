@@ -0,0 +1,280 @@
+use rand::Rng;
+use rand::seq::SliceRandom;
+use std::collections::VecDeque;
+
+use crate::solution::Solution;
+use instance_reader::Instance;
+
+use super::Improvement;
+use super::three_opt::Reconnection;
+use super::tour::{ArrayTour, TWO_LEVEL_DIMENSION, Tour, TwoLevelTour};
+
+#[derive(Debug, Clone, Copy)]
+enum Move {
+    // Replaces (t1, t2) and (t4, t3) by (t2, t3) and (t4, t1), as Tour::move_2opt
+    TwoOpt(usize, usize, usize, usize),
+    // Moves the block between the first two removed edges to the third one,
+    // the edges follow the tour as in Reconnection::Exchange
+    OrOpt([usize; 6]),
+    Swap(usize, usize),
+}
+
+struct CandidateTour<'a, T: Tour> {
+    tour: T,
+    instance: &'a Instance,
+}
+
+impl<T: Tour> CandidateTour<'_, T> {
+    fn c(&self, i: usize, j: usize) -> i32 {
+        self.instance.distance(i, j) as i32
+    }
+
+    fn delta(&self, m: Move) -> i32 {
+        let tour = &self.tour;
+        let c = |i: usize, j: usize| self.c(i, j);
+
+        match m {
+            Move::TwoOpt(t1, t2, t3, t4) => c(t2, t3) + c(t4, t1) - c(t1, t2) - c(t4, t3),
+            Move::OrOpt(edges) => Reconnection::Exchange.delta(edges, self.instance),
+            Move::Swap(a, b) if tour.next(b) == a => self.delta(Move::Swap(b, a)),
+            Move::Swap(a, b) => {
+                let (pa, na, pb, nb) = (tour.prev(a), tour.next(a), tour.prev(b), tour.next(b));
+                if na == b {
+                    c(pa, b) + c(b, a) + c(a, nb) - c(pa, a) - c(a, b) - c(b, nb)
+                } else {
+                    c(pa, b) + c(b, na) + c(pb, a) + c(a, nb)
+                        - c(pa, a)
+                        - c(a, na)
+                        - c(pb, b)
+                        - c(b, nb)
+                }
+            }
+        }
+    }
+
+    // Applies the move and returns the nodes whose edges changed
+    fn apply(&mut self, m: Move) -> Vec<usize> {
+        let tour = &mut self.tour;
+
+        match m {
+            Move::TwoOpt(t1, t2, t3, t4) => {
+                tour.move_2opt(t1, t2, t3, t4);
+                vec![t1, t2, t3, t4]
+            }
+            Move::OrOpt(edges) => {
+                Reconnection::Exchange.apply(tour, edges);
+                edges.to_vec()
+            }
+            Move::Swap(a, b) => {
+                let nodes = vec![tour.prev(a), a, tour.next(a), tour.prev(b), b, tour.next(b)];
+                tour.swap(a, b);
+                nodes
+            }
+        }
+    }
+
+    // Moves that make a adjacent to one of its candidates
+    fn moves(&self, a: usize, candidates: &[usize]) -> Vec<Move> {
+        let tour = &self.tour;
+        let (pa, na) = (tour.prev(a), tour.next(a));
+        // Blocks of up to 3 nodes that start or end at a
+        let after = [a, na, tour.next(na)];
+        let before = [a, pa, tour.prev(pa)];
+        let mut moves = Vec::new();
+
+        for &b in candidates {
+            let (pb, nb) = (tour.prev(b), tour.next(b));
+
+            // 2-opt replacing the edges that leave (or enter) a and b
+            if b != na && nb != a {
+                moves.push(Move::TwoOpt(a, na, nb, b));
+            }
+            if b != pa && pb != a {
+                moves.push(Move::TwoOpt(a, pa, pb, b));
+            }
+
+            for block_size in 1..=3 {
+                // Block starting at a inserted after b
+                let block = &after[..block_size];
+                let last = block[block_size - 1];
+                if !block.contains(&b) && !block.contains(&nb) {
+                    moves.push(Move::OrOpt([pa, a, last, tour.next(last), b, nb]));
+                }
+
+                // Block ending at a inserted before b
+                let block = &before[..block_size];
+                let first = block[block_size - 1];
+                if !block.contains(&pb) && !block.contains(&b) {
+                    moves.push(Move::OrOpt([tour.prev(first), first, a, na, pb, b]));
+                }
+            }
+
+            // Swap a with a neighbour of b so a ends up next to b
+            for x in [nb, pb] {
+                if x != a {
+                    moves.push(Move::Swap(a, x));
+                }
+            }
+        }
+
+        moves
+    }
+}
+
+// Local search over the moves that connect each node to one of its candidates
+// The don't look bits are kept as a queue of the nodes whose bit is off:
+// a node leaves the queue when none of its moves improves,
+// and comes back when one of its edges changes
+// First improvement starts from a shuffled queue and applies the first improving move of a node
+// Moves are applied on a two-level list on large instances, as in Lin-Kernighan
+pub fn local_search(
+    s: &mut Solution,
+    instance: &Instance,
+    candidates: &[Vec<usize>],
+    improvement: Improvement,
+    rng: &mut impl Rng,
+) {
+    if s.sequence.len() - 1 < 5 {
+        return;
+    }
+
+    if instance.dimension < TWO_LEVEL_DIMENSION {
+        let tour = ArrayTour::new(&s.sequence, instance.dimension);
+        run(s, tour, instance, candidates, improvement, rng);
+    } else {
+        let tour = TwoLevelTour::new(&s.sequence, instance.dimension);
+        run(s, tour, instance, candidates, improvement, rng);
+    }
+
+    debug_assert_eq!(
+        s.value,
+        {
+            let mut check = s.clone();
+            check.recalculate(instance);
+            check.value
+        },
+        "the deltas of the moves don't match the cost of the sequence"
+    );
+}
+
+fn run(
+    s: &mut Solution,
+    tour: impl Tour,
+    instance: &Instance,
+    candidates: &[Vec<usize>],
+    improvement: Improvement,
+    rng: &mut impl Rng,
+) {
+    let n = s.sequence.len() - 1;
+    let mut nodes = s.sequence[..n].to_vec();
+    if improvement == Improvement::First {
+        nodes.shuffle(rng);
+    }
+    let mut queue = VecDeque::from(nodes);
+    let mut queued = vec![true; instance.dimension];
+    let mut tour = CandidateTour { tour, instance };
+
+    while let Some(a) = queue.pop_front() {
+        queued[a] = false;
+
+        let mut moves = tour
+            .moves(a, &candidates[a])
+            .into_iter()
+            .map(|m| (tour.delta(m), m));
+        let best = match improvement {
+            Improvement::Best => moves.min_by_key(|&(delta, _)| delta),
+            Improvement::First => moves.find(|&(delta, _)| delta < 0),
+        };
+
+        let Some((delta, m)) = best else {
+            continue;
+        };
+        if delta >= 0 {
+            continue;
+        }
+
+        s.value = (s.value as i32 + delta) as u32;
+        for v in tour.apply(m).into_iter().chain([a]) {
+            if !queued[v] {
+                queued[v] = true;
+                queue.push_back(v);
+            }
+        }
+    }
+
+    s.sequence = tour.tour.sequence();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::SeedableRng;
+    use rand::rngs::StdRng;
+
+    fn random_solution(instance: &Instance, rng: &mut StdRng) -> Solution {
+        let mut sequence: Vec<usize> = (1..instance.dimension).collect();
+        sequence.shuffle(rng);
+        sequence.insert(0, 0);
+        sequence.push(0);
+
+        let mut s = Solution { sequence, value: 0 };
+        s.recalculate(instance);
+        s
+    }
+
+    fn edge(u: usize, v: usize) -> (usize, usize) {
+        (u.min(v), u.max(v))
+    }
+
+    // Applies the move on the tour built from s, the delta must match the new sequence
+    // and every node with a new edge must be reported as changed
+    fn check_move(tour: impl Tour, m: Move, s: &Solution, instance: &Instance) {
+        let n = instance.dimension;
+        let mut tour = CandidateTour { tour, instance };
+        let delta = tour.delta(m);
+        let touched = tour.apply(m);
+
+        let mut moved = Solution {
+            sequence: tour.tour.sequence(),
+            value: 0,
+        };
+        assert_eq!((moved.sequence[0], moved.sequence[n]), (0, 0));
+        let mut nodes = moved.sequence[..n].to_vec();
+        nodes.sort_unstable();
+        assert!(nodes.into_iter().eq(0..n), "{m:?} is not a permutation");
+
+        moved.recalculate(instance);
+        assert_eq!(moved.value as i32, s.value as i32 + delta, "{m:?}");
+
+        let before: Vec<_> = s.sequence.windows(2).map(|w| edge(w[0], w[1])).collect();
+        for w in moved.sequence.windows(2) {
+            if !before.contains(&edge(w[0], w[1])) {
+                assert!(touched.contains(&w[0]) && touched.contains(&w[1]), "{m:?}");
+            }
+        }
+    }
+
+    // Every move of every node, on both tours
+    #[test]
+    fn moves_match_recalculate() {
+        let instance = instance_reader::read_data("instances/gr17.tsp");
+        let n = instance.dimension;
+        let candidates = instance.nearest_neighbours(8);
+        let mut rng = StdRng::seed_from_u64(0);
+
+        for _ in 0..5 {
+            let s = random_solution(&instance, &mut rng);
+            let tour = CandidateTour {
+                tour: ArrayTour::new(&s.sequence, n),
+                instance: &instance,
+            };
+
+            for (a, candidates) in candidates.iter().enumerate() {
+                for m in tour.moves(a, candidates) {
+                    check_move(ArrayTour::new(&s.sequence, n), m, &s, &instance);
+                    check_move(TwoLevelTour::new(&s.sequence, n), m, &s, &instance);
+                }
+            }
+        }
+    }
+}
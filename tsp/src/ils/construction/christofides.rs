@@ -0,0 +1,81 @@
+use instance_reader::Instance;
+
+use super::matching::min_weight_perfect_matching;
+
+// Prim's algorithm over the complete graph, O(n²)
+pub fn minimum_spanning_tree(instance: &Instance) -> Vec<(usize, usize)> {
+    let n = instance.dimension;
+    let mut in_tree = vec![false; n];
+    // Cheapest edge from each node to the tree as (cost, node in the tree)
+    let mut cheapest: Vec<(u32, usize)> = (0..n).map(|v| (instance.distance(0, v), 0)).collect();
+    let mut edges = Vec::with_capacity(n - 1);
+    in_tree[0] = true;
+
+    for _ in 1..n {
+        let v = (0..n)
+            .filter(|&v| !in_tree[v])
+            .min_by_key(|&v| cheapest[v])
+            .unwrap();
+        in_tree[v] = true;
+        edges.push((cheapest[v].1, v));
+
+        for u in (0..n).filter(|&u| !in_tree[u]) {
+            cheapest[u] = cheapest[u].min((instance.distance(v, u), v));
+        }
+    }
+
+    edges
+}
+
+// Hierholzer's algorithm, every node of the multigraph has an even degree
+fn euler_circuit(n: usize, edges: &[(usize, usize)]) -> Vec<usize> {
+    let mut adjacent = vec![Vec::new(); n];
+    for (k, &(i, j)) in edges.iter().enumerate() {
+        adjacent[i].push((j, k));
+        adjacent[j].push((i, k));
+    }
+
+    let mut used = vec![false; edges.len()];
+    let mut stack = vec![0];
+    let mut circuit = Vec::with_capacity(edges.len() + 1);
+    while let Some(&v) = stack.last() {
+        // Edges already walked from the other end are dropped lazily
+        while adjacent[v].last().is_some_and(|&(_, k)| used[k]) {
+            adjacent[v].pop();
+        }
+        match adjacent[v].pop() {
+            Some((u, k)) => {
+                used[k] = true;
+                stack.push(u);
+            }
+            None => {
+                circuit.push(v);
+                stack.pop();
+            }
+        }
+    }
+
+    circuit
+}
+
+// The tree plus a minimum weight perfect matching on its odd degree nodes has an Euler circuit,
+// which becomes a tour by skipping the nodes already visited
+// Within 3/2 of the optimum when the distances respect the triangle inequality
+pub fn christofides(instance: &Instance) -> Vec<usize> {
+    let n = instance.dimension;
+    let mut edges = minimum_spanning_tree(instance);
+
+    let mut degree = vec![0; n];
+    for &(i, j) in &edges {
+        degree[i] += 1;
+        degree[j] += 1;
+    }
+    let odd: Vec<usize> = (0..n).filter(|&v| degree[v] % 2 == 1).collect();
+    edges.extend(min_weight_perfect_matching(&odd, instance));
+
+    let mut visited = vec![false; n];
+    euler_circuit(n, &edges)
+        .into_iter()
+        .filter(|&v| !std::mem::replace(&mut visited[v], true))
+        .collect()
+}
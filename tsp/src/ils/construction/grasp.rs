@@ -1,6 +1,6 @@
 use crate::solution::*;
 use instance_reader::Instance;
-use rand::{Rng, rng};
+use rand::Rng;
 
 // Auxiliary data structure for best insertion
 struct InsertionInfo {
@@ -36,11 +36,11 @@ fn calculate_insertion_cost(s: &Solution, cl: &[usize], instance: &Instance) ->
     insertion_cost
 }
 
-fn choose_three_random(cl: &mut Vec<usize>, instance: &Instance) -> Solution {
+fn choose_three_random(cl: &mut Vec<usize>, instance: &Instance, rng: &mut impl Rng) -> Solution {
     // Choose 3 random clients
     let mut sequence = Vec::new();
     for _ in 0..3 {
-        let index = rng().random_range(0..cl.len());
+        let index = rng.random_range(0..cl.len());
         sequence.push(cl.swap_remove(index));
     }
 
@@ -59,19 +59,20 @@ fn choose_three_random(cl: &mut Vec<usize>, instance: &Instance) -> Solution {
 }
 
 // Constructs a solution with a grasp algorithm using best insertion
-pub fn construction(instance: &Instance) -> Solution {
+// alpha is drawn at every step when it isn't given
+pub fn grasp(instance: &Instance, alpha: Option<f64>, rng: &mut impl Rng) -> Solution {
     // cl is the candidate list to insert into the solution
     let mut cl: Vec<usize> = (1..instance.dimension).collect();
-    let mut s = choose_three_random(&mut cl, instance);
+    let mut s = choose_three_random(&mut cl, instance, rng);
 
     while !cl.is_empty() {
         let mut insertion_cost = calculate_insertion_cost(&s, &cl, instance);
         insertion_cost.sort_unstable_by_key(|x| x.value);
 
-        // Choose a random index from insertion cost but the first values have more priority
-        let alpha: f64 = rng().random_range(1e-10..1.0);
-        let chosen =
-            (rng().random::<u32>() % (alpha * insertion_cost.len() as f64).ceil() as u32) as usize;
+        // Choose uniformly among the best alpha fraction of the insertions, at least one
+        let alpha = alpha.unwrap_or_else(|| rng.random_range(1e-10..1.0));
+        let rcl_size = ((alpha * insertion_cost.len() as f64).ceil() as usize).max(1);
+        let chosen = rng.random_range(0..rcl_size);
 
         let chosen_insertion = &insertion_cost[chosen];
 
@@ -0,0 +1,96 @@
+use instance_reader::Instance;
+
+// Neighbours per node whose edges are considered first
+const NEIGHBOURS: usize = 10;
+
+// Union-find over the nodes, a set is a path of the partial tour
+struct Fragments {
+    parent: Vec<usize>,
+}
+
+impl Fragments {
+    fn find(&mut self, v: usize) -> usize {
+        let mut root = v;
+        while self.parent[root] != root {
+            root = self.parent[root];
+        }
+        let mut v = v;
+        while self.parent[v] != root {
+            let next = self.parent[v];
+            self.parent[v] = root;
+            v = next;
+        }
+        root
+    }
+}
+
+// Adds the edges from shortest to longest when they keep every degree at most 2 and close no cycle
+// Only the edges to the nearest neighbours are sorted, the paths left are then joined
+// by the same rule over the edges between their ends
+pub fn greedy(instance: &Instance) -> Vec<usize> {
+    let n = instance.dimension;
+    let mut fragments = Fragments {
+        parent: (0..n).collect(),
+    };
+    let mut adjacent = vec![Vec::with_capacity(2); n];
+    let mut added = 0;
+
+    let mut add_edges =
+        |mut edges: Vec<(usize, usize)>, adjacent: &mut Vec<Vec<usize>>, added: &mut usize| {
+            edges.sort_unstable_by_key(|&(i, j)| (instance.distance(i, j), i, j));
+            for (i, j) in edges {
+                if *added == n - 1 {
+                    break;
+                }
+                if adjacent[i].len() == 2 || adjacent[j].len() == 2 {
+                    continue;
+                }
+                let (root_i, root_j) = (fragments.find(i), fragments.find(j));
+                if root_i == root_j {
+                    continue;
+                }
+                fragments.parent[root_i] = root_j;
+                adjacent[i].push(j);
+                adjacent[j].push(i);
+                *added += 1;
+            }
+        };
+
+    let candidates = instance
+        .nearest_neighbours(NEIGHBOURS)
+        .into_iter()
+        .enumerate()
+        .flat_map(|(i, neighbours)| {
+            neighbours
+                .into_iter()
+                .filter(move |&j| i < j)
+                .map(move |j| (i, j))
+        })
+        .collect();
+    add_edges(candidates, &mut adjacent, &mut added);
+
+    let ends: Vec<usize> = (0..n).filter(|&v| adjacent[v].len() < 2).collect();
+    let joining = ends
+        .iter()
+        .enumerate()
+        .flat_map(|(k, &i)| ends[k + 1..].iter().map(move |&j| (i, j)))
+        .collect();
+    add_edges(joining, &mut adjacent, &mut added);
+
+    // A single path is left, its ends close the tour
+    let mut tour = Vec::with_capacity(n);
+    let mut previous = usize::MAX;
+    let mut current = (0..n).find(|&v| adjacent[v].len() < 2).unwrap();
+    for _ in 0..n {
+        tour.push(current);
+        let next = adjacent[current]
+            .iter()
+            .copied()
+            .find(|&v| v != previous)
+            .unwrap_or(usize::MAX);
+        previous = current;
+        current = next;
+    }
+
+    tour
+}
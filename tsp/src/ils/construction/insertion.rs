@@ -0,0 +1,111 @@
+use instance_reader::Instance;
+
+// Partial tour kept as the successor of each node
+struct PartialTour<'a> {
+    instance: &'a Instance,
+    next: Vec<usize>,
+    in_tour: Vec<bool>,
+    nodes: Vec<usize>,
+}
+
+impl<'a> PartialTour<'a> {
+    fn new(instance: &'a Instance, start: usize) -> Self {
+        let mut next = vec![usize::MAX; instance.dimension];
+        let mut in_tour = vec![false; instance.dimension];
+        next[start] = start;
+        in_tour[start] = true;
+        PartialTour {
+            instance,
+            next,
+            in_tour,
+            nodes: vec![start],
+        }
+    }
+
+    // Cost of inserting v in the edge that leaves a
+    fn insertion_cost(&self, a: usize, v: usize) -> i64 {
+        let d = |i: usize, j: usize| self.instance.distance(i, j) as i64;
+        let b = self.next[a];
+        d(a, v) + d(v, b) - d(a, b)
+    }
+
+    fn cheapest_edge(&self, v: usize) -> (i64, usize) {
+        self.nodes
+            .iter()
+            .map(|&a| (self.insertion_cost(a, v), a))
+            .min()
+            .unwrap()
+    }
+
+    fn insert(&mut self, a: usize, v: usize) {
+        self.next[v] = self.next[a];
+        self.next[a] = v;
+        self.in_tour[v] = true;
+        self.nodes.push(v);
+    }
+
+    fn outside(&self) -> impl Iterator<Item = usize> + '_ {
+        (0..self.in_tour.len()).filter(|&v| !self.in_tour[v])
+    }
+
+    fn tour(&self) -> Vec<usize> {
+        let start = self.nodes[0];
+        let mut tour = vec![start];
+        let mut v = self.next[start];
+        while v != start {
+            tour.push(v);
+            v = self.next[v];
+        }
+        tour
+    }
+}
+
+// Inserts the node farthest from the tour at its cheapest position, O(n²)
+// The distance from each node to the tour only needs the node inserted last to be updated
+pub fn farthest_insertion(instance: &Instance, start: usize) -> Vec<usize> {
+    let mut tour = PartialTour::new(instance, start);
+    let mut to_tour: Vec<u32> = (0..instance.dimension)
+        .map(|v| instance.distance(start, v))
+        .collect();
+
+    for _ in 1..instance.dimension {
+        let v = tour.outside().max_by_key(|&v| to_tour[v]).unwrap();
+        let (_, a) = tour.cheapest_edge(v);
+        tour.insert(a, v);
+
+        for (u, distance) in to_tour.iter_mut().enumerate() {
+            *distance = (*distance).min(instance.distance(v, u));
+        }
+    }
+
+    tour.tour()
+}
+
+// Inserts the node with the cheapest insertion at that position
+// Each node keeps its cheapest edge, which only has to be compared with the two new edges
+// after an insertion unless the edge itself was removed
+pub fn cheapest_insertion(instance: &Instance, start: usize) -> Vec<usize> {
+    let mut tour = PartialTour::new(instance, start);
+    let mut best: Vec<(i64, usize)> = (0..instance.dimension)
+        .map(|v| (tour.insertion_cost(start, v), start))
+        .collect();
+
+    for _ in 1..instance.dimension {
+        let v = tour.outside().min_by_key(|&v| best[v]).unwrap();
+        let a = best[v].1;
+        tour.insert(a, v);
+
+        let outside: Vec<usize> = tour.outside().collect();
+        for u in outside {
+            best[u] = if best[u].1 == a {
+                tour.cheapest_edge(u)
+            } else {
+                best[u]
+                    .min((tour.insertion_cost(a, u), a))
+                    .min((tour.insertion_cost(v, u), v))
+            };
+        }
+    }
+
+    tour.tour()
+}
@@ -0,0 +1,707 @@
+use instance_reader::Instance;
+
+const NONE: usize = usize::MAX;
+
+// Edmonds' blossom algorithm for a maximum weight matching of maximum cardinality, O(n³)
+// Follows the primal-dual formulation of Galil, "Efficient algorithms for finding maximum
+// matching in graphs": vertices are 0..n and blossoms n..2n, an edge k has the endpoints
+// 2k and 2k + 1, and the dual variables are stored doubled so they stay integers
+struct Blossom {
+    n: usize,
+    edges: Vec<(usize, usize, i64)>,
+    endpoint: Vec<usize>,
+    // Endpoints of the edges that leave each vertex, on the far side
+    neighbend: Vec<Vec<usize>>,
+    // Endpoint of the matched edge on the far side
+    mate: Vec<usize>,
+    // 0 is free, 1 is S, 2 is T, 5 marks a blossom while it's scanned
+    label: Vec<u8>,
+    labelend: Vec<usize>,
+    inblossom: Vec<usize>,
+    blossomparent: Vec<usize>,
+    blossomchilds: Vec<Vec<usize>>,
+    blossombase: Vec<usize>,
+    blossomendps: Vec<Vec<usize>>,
+    bestedge: Vec<usize>,
+    blossombestedges: Vec<Option<Vec<usize>>>,
+    unusedblossoms: Vec<usize>,
+    dualvar: Vec<i64>,
+    allowedge: Vec<bool>,
+    queue: Vec<usize>,
+}
+
+impl Blossom {
+    fn new(n: usize, edges: Vec<(usize, usize, i64)>) -> Self {
+        let max_weight = edges.iter().map(|e| e.2).max().unwrap_or(0).max(0);
+        let endpoint = edges.iter().flat_map(|&(i, j, _)| [i, j]).collect();
+        let mut neighbend = vec![Vec::new(); n];
+        for (k, &(i, j, _)) in edges.iter().enumerate() {
+            neighbend[i].push(2 * k + 1);
+            neighbend[j].push(2 * k);
+        }
+
+        Blossom {
+            n,
+            endpoint,
+            neighbend,
+            mate: vec![NONE; n],
+            label: vec![0; 2 * n],
+            labelend: vec![NONE; 2 * n],
+            inblossom: (0..n).collect(),
+            blossomparent: vec![NONE; 2 * n],
+            blossomchilds: vec![Vec::new(); 2 * n],
+            blossombase: (0..n).chain(std::iter::repeat_n(NONE, n)).collect(),
+            blossomendps: vec![Vec::new(); 2 * n],
+            bestedge: vec![NONE; 2 * n],
+            blossombestedges: vec![None; 2 * n],
+            unusedblossoms: (n..2 * n).collect(),
+            dualvar: std::iter::repeat_n(max_weight, n)
+                .chain(std::iter::repeat_n(0, n))
+                .collect(),
+            allowedge: vec![false; edges.len()],
+            queue: Vec::new(),
+            edges,
+        }
+    }
+
+    fn slack(&self, k: usize) -> i64 {
+        let (i, j, w) = self.edges[k];
+        self.dualvar[i] + self.dualvar[j] - 2 * w
+    }
+
+    fn leaves(&self, b: usize) -> Vec<usize> {
+        if b < self.n {
+            return vec![b];
+        }
+        self.blossomchilds[b]
+            .iter()
+            .flat_map(|&t| self.leaves(t))
+            .collect()
+    }
+
+    fn assign_label(&mut self, w: usize, t: u8, p: usize) {
+        let b = self.inblossom[w];
+        self.label[w] = t;
+        self.label[b] = t;
+        self.labelend[w] = p;
+        self.labelend[b] = p;
+        self.bestedge[w] = NONE;
+        self.bestedge[b] = NONE;
+
+        if t == 1 {
+            // b became an S blossom, its vertices are scanned
+            let leaves = self.leaves(b);
+            self.queue.extend(leaves);
+        } else if t == 2 {
+            // b became a T blossom, its mate becomes S
+            let base = self.blossombase[b];
+            let mate = self.mate[base];
+            self.assign_label(self.endpoint[mate], 1, mate ^ 1);
+        }
+    }
+
+    // Traces back from v and w to find a new blossom or an augmenting path
+    // Returns the base of the new blossom or NONE for an augmenting path
+    fn scan_blossom(&mut self, mut v: usize, mut w: usize) -> usize {
+        let mut path = Vec::new();
+        let mut base = NONE;
+
+        while v != NONE || w != NONE {
+            let mut b = self.inblossom[v];
+            if self.label[b] & 4 != 0 {
+                base = self.blossombase[b];
+                break;
+            }
+            path.push(b);
+            self.label[b] = 5;
+
+            if self.labelend[b] == NONE {
+                // The root of an alternating tree
+                v = NONE;
+            } else {
+                v = self.endpoint[self.labelend[b]];
+                b = self.inblossom[v];
+                v = self.endpoint[self.labelend[b]];
+            }
+
+            if w != NONE {
+                std::mem::swap(&mut v, &mut w);
+            }
+        }
+
+        for b in path {
+            self.label[b] = 1;
+        }
+        base
+    }
+
+    // Builds a blossom with the given base through the S to S edge k
+    fn add_blossom(&mut self, base: usize, k: usize) {
+        let (mut v, mut w, _) = self.edges[k];
+        let bb = self.inblossom[base];
+        let mut bv = self.inblossom[v];
+        let mut bw = self.inblossom[w];
+
+        let b = self.unusedblossoms.pop().unwrap();
+        self.blossombase[b] = base;
+        self.blossomparent[b] = NONE;
+        self.blossomparent[bb] = b;
+
+        let mut path = Vec::new();
+        let mut endps = Vec::new();
+        while bv != bb {
+            self.blossomparent[bv] = b;
+            path.push(bv);
+            endps.push(self.labelend[bv]);
+            v = self.endpoint[self.labelend[bv]];
+            bv = self.inblossom[v];
+        }
+        path.push(bb);
+        path.reverse();
+        endps.reverse();
+        endps.push(2 * k);
+        while bw != bb {
+            self.blossomparent[bw] = b;
+            path.push(bw);
+            endps.push(self.labelend[bw] ^ 1);
+            w = self.endpoint[self.labelend[bw]];
+            bw = self.inblossom[w];
+        }
+
+        self.label[b] = 1;
+        self.labelend[b] = self.labelend[bb];
+        self.dualvar[b] = 0;
+
+        for v in self.leaves_of(&path) {
+            if self.label[self.inblossom[v]] == 2 {
+                // A T vertex inside the new S blossom has to be scanned
+                self.queue.push(v);
+            }
+            self.inblossom[v] = b;
+        }
+
+        // Cheapest edge from the new blossom to each S blossom
+        let mut bestedgeto = vec![NONE; 2 * self.n];
+        for &bv in &path {
+            let lists = match self.blossombestedges[bv].take() {
+                Some(list) => vec![list],
+                None => self
+                    .leaves(bv)
+                    .into_iter()
+                    .map(|v| self.neighbend[v].iter().map(|p| p / 2).collect())
+                    .collect(),
+            };
+            for list in lists {
+                for k in list {
+                    // j is the end outside of the new blossom
+                    let (i, j, _) = self.edges[k];
+                    let j = if self.inblossom[j] == b { i } else { j };
+                    let bj = self.inblossom[j];
+                    if bj != b
+                        && self.label[bj] == 1
+                        && (bestedgeto[bj] == NONE || self.slack(k) < self.slack(bestedgeto[bj]))
+                    {
+                        bestedgeto[bj] = k;
+                    }
+                }
+            }
+            self.bestedge[bv] = NONE;
+        }
+
+        let best: Vec<usize> = bestedgeto.into_iter().filter(|&k| k != NONE).collect();
+        self.bestedge[b] = best
+            .iter()
+            .copied()
+            .min_by_key(|&k| self.slack(k))
+            .unwrap_or(NONE);
+        self.blossombestedges[b] = Some(best);
+        self.blossomchilds[b] = path;
+        self.blossomendps[b] = endps;
+    }
+
+    fn leaves_of(&self, blossoms: &[usize]) -> Vec<usize> {
+        blossoms.iter().flat_map(|&b| self.leaves(b)).collect()
+    }
+
+    // Index into a cyclic list with a possibly negative position
+    fn at(list: &[usize], j: i64) -> usize {
+        list[j.rem_euclid(list.len() as i64) as usize]
+    }
+
+    fn expand_blossom(&mut self, b: usize, endstage: bool) {
+        for s in self.blossomchilds[b].clone() {
+            self.blossomparent[s] = NONE;
+            if s < self.n {
+                self.inblossom[s] = s;
+            } else if endstage && self.dualvar[s] == 0 {
+                self.expand_blossom(s, endstage);
+            } else {
+                for v in self.leaves(s) {
+                    self.inblossom[v] = s;
+                }
+            }
+        }
+
+        // A T blossom expanded in the middle of a stage relabels its children
+        // so the alternating path through it stays valid
+        if !endstage && self.label[b] == 2 {
+            let childs = self.blossomchilds[b].clone();
+            let endps = self.blossomendps[b].clone();
+            let entrychild = self.inblossom[self.endpoint[self.labelend[b] ^ 1]];
+            let mut j = childs.iter().position(|&c| c == entrychild).unwrap() as i64;
+            let (jstep, endptrick) = if j & 1 != 0 {
+                j -= childs.len() as i64;
+                (1, 0)
+            } else {
+                (-1, 1)
+            };
+
+            let mut p = self.labelend[b];
+            while j != 0 {
+                self.label[self.endpoint[p ^ 1]] = 0;
+                let q = Self::at(&endps, j - endptrick);
+                self.label[self.endpoint[q ^ endptrick as usize ^ 1]] = 0;
+                self.assign_label(self.endpoint[p ^ 1], 2, p);
+                self.allowedge[q / 2] = true;
+                j += jstep;
+                p = Self::at(&endps, j - endptrick) ^ endptrick as usize;
+                self.allowedge[p / 2] = true;
+                j += jstep;
+            }
+
+            let bv = Self::at(&childs, j);
+            self.label[self.endpoint[p ^ 1]] = 2;
+            self.label[bv] = 2;
+            self.labelend[self.endpoint[p ^ 1]] = p;
+            self.labelend[bv] = p;
+            self.bestedge[bv] = NONE;
+            j += jstep;
+
+            while Self::at(&childs, j) != entrychild {
+                let bv = Self::at(&childs, j);
+                if self.label[bv] == 1 {
+                    j += jstep;
+                    continue;
+                }
+                let labelled = self.leaves(bv).into_iter().find(|&v| self.label[v] != 0);
+                if let Some(v) = labelled {
+                    self.label[v] = 0;
+                    self.label[self.endpoint[self.mate[self.blossombase[bv]]]] = 0;
+                    self.assign_label(v, 2, self.labelend[v]);
+                }
+                j += jstep;
+            }
+        }
+
+        self.label[b] = u8::MAX;
+        self.labelend[b] = NONE;
+        self.blossomchilds[b].clear();
+        self.blossomendps[b].clear();
+        self.blossombase[b] = NONE;
+        self.blossombestedges[b] = None;
+        self.bestedge[b] = NONE;
+        self.unusedblossoms.push(b);
+    }
+
+    // Swaps the matched and unmatched edges on the path from vertex v to the base of b
+    fn augment_blossom(&mut self, b: usize, v: usize) {
+        let mut t = v;
+        while self.blossomparent[t] != b {
+            t = self.blossomparent[t];
+        }
+        if t >= self.n {
+            self.augment_blossom(t, v);
+        }
+
+        let childs = self.blossomchilds[b].clone();
+        let endps = self.blossomendps[b].clone();
+        let i = childs.iter().position(|&c| c == t).unwrap();
+        let mut j = i as i64;
+        let (jstep, endptrick) = if i & 1 != 0 {
+            j -= childs.len() as i64;
+            (1, 0)
+        } else {
+            (-1, 1)
+        };
+
+        while j != 0 {
+            j += jstep;
+            let t = Self::at(&childs, j);
+            let p = Self::at(&endps, j - endptrick) ^ endptrick as usize;
+            if t >= self.n {
+                self.augment_blossom(t, self.endpoint[p]);
+            }
+            j += jstep;
+            let t = Self::at(&childs, j);
+            if t >= self.n {
+                self.augment_blossom(t, self.endpoint[p ^ 1]);
+            }
+            self.mate[self.endpoint[p]] = p ^ 1;
+            self.mate[self.endpoint[p ^ 1]] = p;
+        }
+
+        self.blossomchilds[b].rotate_left(i);
+        self.blossomendps[b].rotate_left(i);
+        self.blossombase[b] = self.blossombase[self.blossomchilds[b][0]];
+    }
+
+    // Swaps the matched and unmatched edges on the augmenting path through the edge k
+    fn augment_matching(&mut self, k: usize) {
+        let (v, w, _) = self.edges[k];
+        for (mut s, mut p) in [(v, 2 * k + 1), (w, 2 * k)] {
+            loop {
+                let bs = self.inblossom[s];
+                if bs >= self.n {
+                    self.augment_blossom(bs, s);
+                }
+                self.mate[s] = p;
+                if self.labelend[bs] == NONE {
+                    // Reached the root of the tree
+                    break;
+                }
+                let t = self.endpoint[self.labelend[bs]];
+                let bt = self.inblossom[t];
+                s = self.endpoint[self.labelend[bt]];
+                let j = self.endpoint[self.labelend[bt] ^ 1];
+                if bt >= self.n {
+                    self.augment_blossom(bt, j);
+                }
+                self.mate[j] = self.labelend[bt];
+                p = self.labelend[bt] ^ 1;
+            }
+        }
+    }
+
+    // Scans the queue of S vertices, returns whether the matching was augmented
+    fn scan_queue(&mut self) -> bool {
+        while let Some(v) = self.queue.pop() {
+            for idx in 0..self.neighbend[v].len() {
+                let p = self.neighbend[v][idx];
+                let k = p / 2;
+                let w = self.endpoint[p];
+                if self.inblossom[v] == self.inblossom[w] {
+                    continue;
+                }
+
+                let mut kslack = 0;
+                if !self.allowedge[k] {
+                    kslack = self.slack(k);
+                    if kslack <= 0 {
+                        self.allowedge[k] = true;
+                    }
+                }
+
+                if self.allowedge[k] {
+                    if self.label[self.inblossom[w]] == 0 {
+                        self.assign_label(w, 2, p ^ 1);
+                    } else if self.label[self.inblossom[w]] == 1 {
+                        let base = self.scan_blossom(v, w);
+                        if base != NONE {
+                            self.add_blossom(base, k);
+                        } else {
+                            self.augment_matching(k);
+                            return true;
+                        }
+                    } else if self.label[w] == 0 {
+                        // w is inside a T blossom but not reached yet
+                        self.label[w] = 2;
+                        self.labelend[w] = p ^ 1;
+                    }
+                } else if self.label[self.inblossom[w]] == 1 {
+                    let b = self.inblossom[v];
+                    if self.bestedge[b] == NONE || kslack < self.slack(self.bestedge[b]) {
+                        self.bestedge[b] = k;
+                    }
+                } else if self.label[w] == 0
+                    && (self.bestedge[w] == NONE || kslack < self.slack(self.bestedge[w]))
+                {
+                    self.bestedge[w] = k;
+                }
+            }
+        }
+        false
+    }
+
+    // Changes the dual variables by the largest amount that keeps them feasible
+    // Returns false once no augmenting path is left
+    fn update_duals(&mut self) -> bool {
+        let n = self.n;
+        // 2: edge from S to a free vertex, 3: edge between S blossoms, 4: T blossom to expand
+        let mut delta_type = 0;
+        let mut delta = 0;
+        let mut delta_edge = NONE;
+        let mut delta_blossom = NONE;
+
+        for v in 0..n {
+            if self.label[self.inblossom[v]] == 0 && self.bestedge[v] != NONE {
+                let d = self.slack(self.bestedge[v]);
+                if delta_type == 0 || d < delta {
+                    delta = d;
+                    delta_type = 2;
+                    delta_edge = self.bestedge[v];
+                }
+            }
+        }
+        for b in 0..2 * n {
+            if self.blossomparent[b] == NONE && self.label[b] == 1 && self.bestedge[b] != NONE {
+                let d = self.slack(self.bestedge[b]) / 2;
+                if delta_type == 0 || d < delta {
+                    delta = d;
+                    delta_type = 3;
+                    delta_edge = self.bestedge[b];
+                }
+            }
+        }
+        for b in n..2 * n {
+            if self.blossombase[b] != NONE
+                && self.blossomparent[b] == NONE
+                && self.label[b] == 2
+                && (delta_type == 0 || self.dualvar[b] < delta)
+            {
+                delta = self.dualvar[b];
+                delta_type = 4;
+                delta_blossom = b;
+            }
+        }
+        if delta_type == 0 {
+            // Nothing can grow anymore, the matching has maximum cardinality
+            delta_type = 1;
+            delta = self.dualvar[..n].iter().copied().min().unwrap().max(0);
+        }
+
+        for v in 0..n {
+            match self.label[self.inblossom[v]] {
+                1 => self.dualvar[v] -= delta,
+                2 => self.dualvar[v] += delta,
+                _ => {}
+            }
+        }
+        for b in n..2 * n {
+            if self.blossombase[b] != NONE && self.blossomparent[b] == NONE {
+                match self.label[b] {
+                    1 => self.dualvar[b] += delta,
+                    2 => self.dualvar[b] -= delta,
+                    _ => {}
+                }
+            }
+        }
+
+        match delta_type {
+            1 => return false,
+            2 => {
+                self.allowedge[delta_edge] = true;
+                let (mut i, j, _) = self.edges[delta_edge];
+                if self.label[self.inblossom[i]] == 0 {
+                    i = j;
+                }
+                self.queue.push(i);
+            }
+            3 => {
+                self.allowedge[delta_edge] = true;
+                let (i, _, _) = self.edges[delta_edge];
+                self.queue.push(i);
+            }
+            _ => self.expand_blossom(delta_blossom, false),
+        }
+        true
+    }
+
+    fn solve(mut self) -> Vec<usize> {
+        let n = self.n;
+        for _ in 0..n {
+            // Each stage looks for one augmenting path
+            self.label.fill(0);
+            self.bestedge.fill(NONE);
+            for b in n..2 * n {
+                self.blossombestedges[b] = None;
+            }
+            self.allowedge.fill(false);
+            self.queue.clear();
+
+            for v in 0..n {
+                if self.mate[v] == NONE && self.label[self.inblossom[v]] == 0 {
+                    self.assign_label(v, 1, NONE);
+                }
+            }
+
+            let augmented = loop {
+                if self.scan_queue() {
+                    break true;
+                }
+                if !self.update_duals() {
+                    break false;
+                }
+            };
+            if !augmented {
+                break;
+            }
+
+            // S blossoms with a zero dual are expanded at the end of the stage
+            for b in n..2 * n {
+                if self.blossomparent[b] == NONE
+                    && self.blossombase[b] != NONE
+                    && self.label[b] == 1
+                    && self.dualvar[b] == 0
+                {
+                    self.expand_blossom(b, true);
+                }
+            }
+        }
+
+        (0..n)
+            .map(|v| {
+                let p = self.mate[v];
+                if p == NONE { NONE } else { self.endpoint[p] }
+            })
+            .collect()
+    }
+}
+
+// Minimum weight perfect matching between the nodes, there must be an even number of them
+// The complete graph is matched with the weights flipped, since a maximum cardinality
+// matching is perfect there
+pub fn min_weight_perfect_matching(nodes: &[usize], instance: &Instance) -> Vec<(usize, usize)> {
+    let k = nodes.len();
+    let max_distance = nodes
+        .iter()
+        .flat_map(|&i| nodes.iter().map(move |&j| instance.distance(i, j)))
+        .max()
+        .unwrap_or(0) as i64;
+
+    let edges = (0..k)
+        .flat_map(|a| (a + 1..k).map(move |b| (a, b)))
+        .map(|(a, b)| {
+            (
+                a,
+                b,
+                max_distance - instance.distance(nodes[a], nodes[b]) as i64,
+            )
+        })
+        .collect();
+    let mate = Blossom::new(k, edges).solve();
+
+    (0..k)
+        .filter(|&a| a < mate[a])
+        .map(|a| (nodes[a], nodes[mate[a]]))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::christofides::minimum_spanning_tree;
+    use super::*;
+    use rand::rngs::StdRng;
+    use rand::seq::SliceRandom;
+    use rand::{Rng, SeedableRng};
+
+    // Largest (cardinality, weight) of a matching, by dynamic programming over subsets of vertices
+    fn best_matching(n: usize, edges: &[(usize, usize, i64)]) -> (usize, i64) {
+        let mut weight = vec![vec![None; n]; n];
+        for &(i, j, w) in edges {
+            weight[i][j] = Some(w);
+            weight[j][i] = Some(w);
+        }
+
+        let mut best = vec![(0, 0); 1 << n];
+        for set in 1..1usize << n {
+            // The lowest vertex of the set is left unmatched or matched to another one of the set
+            let v = set.trailing_zeros() as usize;
+            let rest = set & !(1 << v);
+            best[set] = best[rest];
+            for u in (0..n).filter(|&u| rest & (1 << u) != 0) {
+                if let Some(w) = weight[v][u] {
+                    let (count, value) = best[rest & !(1 << u)];
+                    best[set] = best[set].max((count + 1, value + w));
+                }
+            }
+        }
+
+        best[(1 << n) - 1]
+    }
+
+    #[test]
+    fn blossom_matches_dp_on_random_graphs() {
+        let mut rng = StdRng::seed_from_u64(0);
+
+        for _ in 0..2000 {
+            let n = rng.random_range(1..=12);
+            let density = rng.random_range(0.1..1.0);
+            let mut edges = Vec::new();
+            for i in 0..n {
+                for j in i + 1..n {
+                    if rng.random_bool(density) {
+                        edges.push((i, j, rng.random_range(0..=100)));
+                    }
+                }
+            }
+
+            let mate = Blossom::new(n, edges.clone()).solve();
+            let mut count = 0;
+            let mut value = 0;
+            for &(i, j, w) in &edges {
+                if mate[i] == j {
+                    assert_eq!(mate[j], i);
+                    count += 1;
+                    value += w;
+                }
+            }
+            assert_eq!(
+                (0..n).filter(|&v| mate[v] != NONE).count(),
+                2 * count,
+                "matched along a missing edge"
+            );
+            assert_eq!((count, value), best_matching(n, &edges), "{edges:?}");
+        }
+    }
+
+    fn assert_min_perfect(nodes: &[usize], instance: &Instance) {
+        let matching = min_weight_perfect_matching(nodes, instance);
+        let mut matched: Vec<usize> = matching.iter().flat_map(|&(i, j)| [i, j]).collect();
+        matched.sort_unstable();
+        let mut expected = nodes.to_vec();
+        expected.sort_unstable();
+        assert_eq!(matched, expected, "not a perfect matching");
+
+        // A perfect matching of smallest weight has the largest flipped weight
+        let edges: Vec<(usize, usize, i64)> = (0..nodes.len())
+            .flat_map(|a| (a + 1..nodes.len()).map(move |b| (a, b)))
+            .map(|(a, b)| (a, b, -(instance.distance(nodes[a], nodes[b]) as i64)))
+            .collect();
+        let (_, best) = best_matching(nodes.len(), &edges);
+        let cost: i64 = matching
+            .iter()
+            .map(|&(i, j)| instance.distance(i, j) as i64)
+            .sum();
+        assert_eq!(cost, -best);
+    }
+
+    #[test]
+    fn perfect_matching_matches_dp_on_instances() {
+        let mut rng = StdRng::seed_from_u64(0);
+
+        for name in ["burma14", "ulysses16", "gr17", "bays29", "att48", "st70"] {
+            let instance = instance_reader::read_data(&format!("instances/{name}.tsp"));
+
+            // Odd degree nodes of the spanning tree, as in the Christofides construction
+            let mut degree = vec![0; instance.dimension];
+            for (i, j) in minimum_spanning_tree(&instance) {
+                degree[i] += 1;
+                degree[j] += 1;
+            }
+            let odd: Vec<usize> = (0..instance.dimension)
+                .filter(|&v| degree[v] % 2 == 1)
+                .collect();
+            if odd.len() <= 20 {
+                assert_min_perfect(&odd, &instance);
+            }
+
+            // Random sets of nodes
+            let mut nodes: Vec<usize> = (0..instance.dimension).collect();
+            for _ in 0..50 {
+                nodes.shuffle(&mut rng);
+                let k = 2 * rng.random_range(0..=8.min(instance.dimension / 2));
+                assert_min_perfect(&nodes[..k], &instance);
+            }
+        }
+    }
+}
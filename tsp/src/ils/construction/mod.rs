@@ -0,0 +1,80 @@
+mod christofides;
+mod grasp;
+mod greedy;
+mod insertion;
+mod matching;
+mod nearest_neighbour;
+mod space_filling_curve;
+
+use crate::solution::Solution;
+use instance_reader::Instance;
+use rand::Rng;
+
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub enum Construction {
+    // Random insertion among the cheapest ones, O(n³)
+    #[default]
+    Grasp,
+    // Starts at a random node, O(n²)
+    NearestNeighbour,
+    // Shortest edges first over the nearest neighbour graph
+    Greedy,
+    // Minimum spanning tree plus a minimum weight perfect matching on its odd nodes, O(n³)
+    Christofides,
+    // Order of the nodes along a Hilbert curve, needs coordinates, O(n log n)
+    SpaceFillingCurve,
+    // Inserts the node farthest from the tour at its cheapest position, O(n²)
+    FarthestInsertion,
+    // Inserts the node with the cheapest insertion
+    CheapestInsertion,
+}
+
+// alpha is only used by the GRASP
+pub fn construction(
+    kind: Construction,
+    alpha: Option<f64>,
+    instance: &Instance,
+    rng: &mut impl Rng,
+) -> Solution {
+    if instance.dimension < 4 {
+        return from_tour((0..instance.dimension).collect(), instance);
+    }
+
+    match kind {
+        Construction::Grasp => grasp::grasp(instance, alpha, rng),
+        Construction::NearestNeighbour => {
+            let start = rng.random_range(0..instance.dimension);
+            from_tour(
+                nearest_neighbour::nearest_neighbour(instance, start),
+                instance,
+            )
+        }
+        Construction::Greedy => from_tour(greedy::greedy(instance), instance),
+        Construction::Christofides => from_tour(christofides::christofides(instance), instance),
+        Construction::SpaceFillingCurve => {
+            from_tour(space_filling_curve::hilbert(instance), instance)
+        }
+        Construction::FarthestInsertion => {
+            let start = rng.random_range(0..instance.dimension);
+            from_tour(insertion::farthest_insertion(instance, start), instance)
+        }
+        Construction::CheapestInsertion => {
+            let start = rng.random_range(0..instance.dimension);
+            from_tour(insertion::cheapest_insertion(instance, start), instance)
+        }
+    }
+}
+
+// Solution from a cyclic order of every node, rotated to start and end at node 0
+fn from_tour(mut tour: Vec<usize>, instance: &Instance) -> Solution {
+    let start = tour.iter().position(|&v| v == 0).unwrap();
+    tour.rotate_left(start);
+    tour.push(0);
+
+    let mut s = Solution {
+        sequence: tour,
+        value: 0,
+    };
+    s.recalculate(instance);
+    s
+}
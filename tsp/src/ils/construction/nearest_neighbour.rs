@@ -0,0 +1,23 @@
+use instance_reader::Instance;
+
+// Goes to the closest unvisited node until every node is visited
+pub fn nearest_neighbour(instance: &Instance, start: usize) -> Vec<usize> {
+    let n = instance.dimension;
+    let mut visited = vec![false; n];
+    let mut tour = Vec::with_capacity(n);
+
+    let mut current = start;
+    visited[current] = true;
+    tour.push(current);
+
+    for _ in 1..n {
+        current = (0..n)
+            .filter(|&v| !visited[v])
+            .min_by_key(|&v| instance.distance(current, v))
+            .unwrap();
+        visited[current] = true;
+        tour.push(current);
+    }
+
+    tour
+}
@@ -0,0 +1,54 @@
+use instance_reader::Instance;
+
+// The coordinates are scaled to a grid of 2^16 x 2^16 cells
+const GRID: u64 = 1 << 16;
+
+// Distance along the Hilbert curve that fills the grid to the cell (x, y)
+fn hilbert_index(mut x: u64, mut y: u64) -> u64 {
+    let mut d = 0;
+    let mut s = GRID / 2;
+    while s > 0 {
+        let rx = (x & s > 0) as u64;
+        let ry = (y & s > 0) as u64;
+        d += s * s * ((3 * rx) ^ ry);
+
+        // Rotate the quadrant so the curve inside it has the base orientation
+        if ry == 0 {
+            if rx == 1 {
+                x = GRID - 1 - x;
+                y = GRID - 1 - y;
+            }
+            std::mem::swap(&mut x, &mut y);
+        }
+        s /= 2;
+    }
+    d
+}
+
+// Visits the nodes in the order of a Hilbert curve through the bounding box of the coordinates
+pub fn hilbert(instance: &Instance) -> Vec<usize> {
+    let coords = instance
+        .coords()
+        .expect("The space filling curve needs the coordinates of the nodes");
+
+    let bound = |pick: fn(&(f32, f32)) -> f32| {
+        coords
+            .iter()
+            .map(pick)
+            .fold((f32::MAX, f32::MIN), |(min, max), v| {
+                (min.min(v), max.max(v))
+            })
+    };
+    let (min_x, max_x) = bound(|c| c.0);
+    let (min_y, max_y) = bound(|c| c.1);
+    // Both axes use the same scale so the curve isn't stretched
+    let side = (max_x - min_x).max(max_y - min_y).max(f32::EPSILON);
+    let cell = |v: f32, min: f32| (((v - min) / side) * (GRID - 1) as f32) as u64;
+
+    let mut tour: Vec<usize> = (0..instance.dimension).collect();
+    tour.sort_by_cached_key(|&v| {
+        let (x, y) = coords[v];
+        hilbert_index(cell(x, min_x), cell(y, min_y))
+    });
+    tour
+}
@@ -0,0 +1,195 @@
+use rand::Rng;
+use rand::seq::SliceRandom;
+use std::collections::VecDeque;
+
+use crate::solution::Solution;
+use instance_reader::Instance;
+
+use super::Improvement;
+use super::tour::{ArrayTour, TWO_LEVEL_DIMENSION, Tour, TwoLevelTour};
+
+// Deepest chain of 2-opt moves tried from one starting edge
+const MAX_DEPTH: usize = 50;
+
+struct Search<'a, T: Tour> {
+    tour: T,
+    instance: &'a Instance,
+    neighbours: &'a [Vec<usize>],
+    // Stops a chain at the first tour that improves
+    first: bool,
+}
+
+impl<T: Tour> Search<'_, T> {
+    fn c(&self, i: usize, j: usize) -> i32 {
+        self.instance.distance(i, j) as i32
+    }
+
+    // Candidates for t3 after breaking (t1, t2) with gain g, as (t3, t4, g1)
+    // t4 comes right before t3 in the direction that goes from t1 to t2,
+    // so (t4, t1) closes the tour
+    fn candidates(
+        &self,
+        t1: usize,
+        t2: usize,
+        g: i32,
+        added: &[(usize, usize)],
+    ) -> Vec<(usize, usize, i32)> {
+        let forward = self.tour.next(t1) == t2;
+        let succ = if forward {
+            self.tour.next(t2)
+        } else {
+            self.tour.prev(t2)
+        };
+
+        let mut candidates: Vec<_> = self.neighbours[t2]
+            .iter()
+            .map(|&t3| (t3, g - self.c(t2, t3)))
+            .take_while(|&(_, g1)| g1 > 0)
+            .filter(|&(t3, _)| t3 != t1 && t3 != succ)
+            .map(|(t3, g1)| {
+                let t4 = if forward {
+                    self.tour.prev(t3)
+                } else {
+                    self.tour.next(t3)
+                };
+                (t3, t4, g1)
+            })
+            .filter(|&(t3, t4, _)| !added.contains(&(t3, t4)) && !added.contains(&(t4, t3)))
+            .collect();
+
+        // The edge removed next should be as long as possible
+        candidates.sort_by_key(|&(t3, t4, g1)| -(g1 + self.c(t3, t4)));
+        candidates
+    }
+
+    // Chain of 2-opt moves starting by breaking (t1, t2)
+    // Every level also breaks the edge that closed the tour in the previous one,
+    // and only the best candidate is followed after the first level
+    // First improvement stops the chain at the first tour that improves
+    // Returns the gain and the nodes whose edges changed, the tour is left unchanged without a gain
+    fn improve(&mut self, t1: usize, t2: usize) -> Option<(i32, Vec<usize>)> {
+        let first = self.candidates(t1, t2, self.c(t1, t2), &[]);
+
+        for (t3, t4, g1) in first {
+            let mut moves = vec![(t1, t2, t3, t4)];
+            let mut added = vec![(t2, t3)];
+            self.tour.move_2opt(t1, t2, t3, t4);
+
+            let mut g = g1 + self.c(t3, t4);
+            let mut best_gain = g - self.c(t4, t1);
+            let mut best_depth = 1;
+            let mut t2 = t4;
+
+            while moves.len() < MAX_DEPTH && !(self.first && best_gain > 0) {
+                let Some(&(t3, t4, g1)) = self.candidates(t1, t2, g, &added).first() else {
+                    break;
+                };
+                self.tour.move_2opt(t1, t2, t3, t4);
+                moves.push((t1, t2, t3, t4));
+                added.push((t2, t3));
+
+                g = g1 + self.c(t3, t4);
+                if g - self.c(t4, t1) > best_gain {
+                    best_gain = g - self.c(t4, t1);
+                    best_depth = moves.len();
+                }
+                t2 = t4;
+            }
+
+            // Undo the moves after the best tour of the chain
+            let keep = if best_gain > 0 { best_depth } else { 0 };
+            for &(t1, t2, t3, t4) in moves[keep..].iter().rev() {
+                self.tour.move_2opt(t1, t4, t3, t2);
+            }
+
+            if best_gain > 0 {
+                let touched = moves[..keep]
+                    .iter()
+                    .flat_map(|&(t1, t2, t3, t4)| [t1, t2, t3, t4])
+                    .collect();
+                return Some((best_gain, touched));
+            }
+        }
+
+        None
+    }
+}
+
+// Lin-Kernighan style search made of chains of 2-opt moves between neighbouring nodes
+// Nodes are kept in a queue as in the candidate search, a node leaves it
+// when no chain starting at one of its edges improves the tour
+// First improvement starts from a shuffled queue
+pub fn local_search(
+    s: &mut Solution,
+    instance: &Instance,
+    neighbours: &[Vec<usize>],
+    improvement: Improvement,
+    rng: &mut impl Rng,
+) {
+    if s.sequence.len() - 1 < 5 {
+        return;
+    }
+
+    if instance.dimension < TWO_LEVEL_DIMENSION {
+        let tour = ArrayTour::new(&s.sequence, instance.dimension);
+        run(s, tour, instance, neighbours, improvement, rng);
+    } else {
+        let tour = TwoLevelTour::new(&s.sequence, instance.dimension);
+        run(s, tour, instance, neighbours, improvement, rng);
+    }
+
+    debug_assert_eq!(
+        s.value,
+        {
+            let mut check = s.clone();
+            check.recalculate(instance);
+            check.value
+        },
+        "the gains of the chains don't match the cost of the sequence"
+    );
+}
+
+fn run(
+    s: &mut Solution,
+    tour: impl Tour,
+    instance: &Instance,
+    neighbours: &[Vec<usize>],
+    improvement: Improvement,
+    rng: &mut impl Rng,
+) {
+    let n = s.sequence.len() - 1;
+    let mut search = Search {
+        tour,
+        instance,
+        neighbours,
+        first: improvement == Improvement::First,
+    };
+
+    let mut nodes = s.sequence[..n].to_vec();
+    if search.first {
+        nodes.shuffle(rng);
+    }
+    let mut queue = VecDeque::from(nodes);
+    let mut queued = vec![true; instance.dimension];
+
+    while let Some(t1) = queue.pop_front() {
+        queued[t1] = false;
+
+        for t2 in [search.tour.next(t1), search.tour.prev(t1)] {
+            let Some((gain, touched)) = search.improve(t1, t2) else {
+                continue;
+            };
+
+            s.value = (s.value as i32 - gain) as u32;
+            for v in touched.into_iter().chain([t1]) {
+                if !queued[v] {
+                    queued[v] = true;
+                    queue.push_back(v);
+                }
+            }
+            break;
+        }
+    }
+
+    s.sequence = search.tour.sequence();
+}
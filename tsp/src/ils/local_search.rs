@@ -1,175 +1,528 @@
-use rand::{Rng, rng};
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::{Rng, SeedableRng};
 
 use crate::solution::Solution;
 use instance_reader::Instance;
 
-#[derive(Clone)]
+use super::three_opt::{Reconnection, best_3opt};
+use super::tour::{ArrayTour, TWO_LEVEL_DIMENSION, Tour, TwoLevelTour};
+
+#[derive(Debug, Clone)]
 enum Searches {
     Swap,
     TwoOpt,
     OrOpt(usize),
+    // Or-opt that inserts the block in the opposite direction
+    ReversedOrOpt(usize),
+    SegmentInsertion(SegmentInsertion),
+    ThreeOpt,
+}
+
+// Or-opt with blocks of any size up to max_size, also reinserted reversed when reversed is set
+#[derive(Debug, Clone, Copy)]
+pub struct SegmentInsertion {
+    pub max_size: usize,
+    pub reversed: bool,
+}
+
+// Best improvement applies the best move of a neighbourhood,
+// first improvement applies the first improving move found in a random order
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub enum Improvement {
+    #[default]
+    Best,
+    First,
 }
 
-fn best_swap(s: &mut Solution, instance: &Instance) -> bool {
-    let mut best_delta = 0;
-    let mut best_i = usize::MAX;
-    let mut best_j = usize::MAX;
+// Order in which a neighbourhood goes through the nodes
+#[derive(Clone, Copy)]
+pub struct Scan {
+    pub first: bool,
+    seed: u64,
+}
+
+impl Scan {
+    // The generator is only used in first improvement
+    pub fn new(improvement: Improvement, rng: &mut impl Rng) -> Self {
+        let first = improvement == Improvement::First;
+        let seed = if first { rng.random() } else { 0 };
+        Scan { first, seed }
+    }
+
+    // Nodes in the order of the sequence, shuffled in first improvement
+    // A scan always gives the same order, a new one is drawn for every neighbourhood
+    pub fn nodes(self, tour: &impl Tour) -> Vec<usize> {
+        let mut nodes: Vec<usize> = tour.walk(0).collect();
+        if self.first {
+            nodes.shuffle(&mut StdRng::seed_from_u64(self.seed));
+        }
+        nodes
+    }
+}
+
+// Nodes from a to the end of the sequence, closed by node 0, which is alone when a is 0
+fn to_end(tour: &impl Tour, a: usize) -> impl Iterator<Item = usize> + '_ {
+    tour.walk(a).take_while(|&v| v != 0).chain([0])
+}
+
+// Consecutive pairs of the nodes, which are the edges of a path
+fn edges(path: impl Iterator<Item = usize>) -> impl Iterator<Item = (usize, usize)> {
+    path.scan(None, |previous, v| {
+        Some(previous.replace(v).map(|u| (u, v)))
+    })
+    .flatten()
+}
+
+// The searches return the delta of the move they applied, 0 when none improves
+// Node 0 is never moved, so it stays at both ends of the sequence
+fn best_swap(tour: &mut impl Tour, scan: Scan, instance: &Instance) -> i32 {
+    let mut best = (0, 0, 0);
 
     let c = |i: usize, j: usize| instance.distance(i, j) as i32;
 
-    let iter = s.sequence.windows(3).enumerate();
-    for (i, window) in iter {
-        let vi_prev = window[0];
-        let vi = window[1];
-        let vi_next = window[2];
+    'scan: for vi in scan.nodes(tour) {
+        if vi == 0 {
+            continue;
+        }
+        let vi_prev = tour.prev(vi);
+        let vi_next = tour.next(vi);
 
         let removal_delta = -(c(vi_prev, vi) + c(vi, vi_next));
 
-        let inner_iter = s.sequence[i + 2..].windows(3).enumerate();
-        for (j_offset, window_j) in inner_iter {
-            let j = i + 3 + j_offset;
-            let vj_prev = window_j[0];
-            let vj = window_j[1];
-            let vj_next = window_j[2];
-
+        // Nodes after vi_next, which aren't next to vi
+        let mut path = to_end(tour, vi_next).skip(1);
+        let Some(mut vj) = path.next() else {
+            continue;
+        };
+        let mut vj_prev = vi_next;
+        for vj_next in path {
             let delta = c(vi_prev, vj) + c(vj, vi_next) - c(vj_prev, vj) - c(vj, vj_next)
                 + c(vj_prev, vi)
                 + c(vi, vj_next)
                 + removal_delta;
 
-            if delta < best_delta {
-                best_delta = delta;
-                best_i = i + 1;
-                best_j = j;
+            if delta < best.0 {
+                best = (delta, vi, vj);
+                if scan.first {
+                    break 'scan;
+                }
             }
+            (vj_prev, vj) = (vj, vj_next);
         }
     }
 
-    if best_delta < 0 {
-        s.sequence.swap(best_i, best_j);
-        s.value = (s.value as i32 + best_delta) as u32;
-
-        return true;
+    let (delta, vi, vj) = best;
+    if delta < 0 {
+        tour.swap(vi, vj);
     }
-
-    false
+    delta
 }
 
-fn best_2opt(s: &mut Solution, instance: &Instance) -> bool {
-    let mut best_delta = 0;
-    let mut best_i = usize::MAX;
-    let mut best_j = usize::MAX;
+fn best_2opt(tour: &mut impl Tour, scan: Scan, instance: &Instance) -> i32 {
+    let mut best = (0, [0; 4]);
 
     let c = |i: usize, j: usize| instance.distance(i, j) as i32;
 
-    let iter = s.sequence.windows(2).enumerate();
-    for (i, window) in iter {
-        let vi_prev = window[0];
-        let vi = window[1];
-
-        let inner_iter = s.sequence[i + 2..].windows(2).enumerate();
-        for (j_offset, window_j) in inner_iter {
-            let j = i + 2 + j_offset;
-
-            let vj = window_j[0];
-            let vj_next = window_j[1];
+    'scan: for vi_prev in scan.nodes(tour) {
+        let vi = tour.next(vi_prev);
+        if vi == 0 {
+            continue;
+        }
 
+        for (vj, vj_next) in edges(to_end(tour, tour.next(vi))) {
             let delta = c(vi, vj_next) + c(vj, vi_prev) - c(vi_prev, vi) - c(vj, vj_next);
 
-            if delta < best_delta {
-                best_delta = delta;
-                best_i = i + 1;
-                best_j = j;
+            if delta < best.0 {
+                best = (delta, [vi_prev, vi, vj_next, vj]);
+                if scan.first {
+                    break 'scan;
+                }
             }
         }
     }
 
-    if best_delta < 0 {
-        s.sequence[best_i..=best_j].reverse();
-        s.value = (s.value as i32 + best_delta) as u32;
-
-        return true;
+    let (delta, [t1, t2, t3, t4]) = best;
+    if delta < 0 {
+        tour.move_2opt(t1, t2, t3, t4);
     }
-
-    false
+    delta
 }
 
-fn best_oropt(s: &mut Solution, block_size: usize, instance: &Instance) -> bool {
-    let mut best_delta = 0;
-    let mut best_i = usize::MAX;
-    let mut best_j = usize::MAX;
+// Best or-opt move for one block size as its delta and the removed edges in tour order
+// The delta is 0 when no move improves the solution
+fn find_oropt(
+    tour: &impl Tour,
+    scan: Scan,
+    block_size: usize,
+    reversed: bool,
+    instance: &Instance,
+) -> (i32, [usize; 6]) {
+    let mut best = (0, [0; 6]);
 
     let c = |i: usize, j: usize| instance.distance(i, j) as i32;
 
-    let iter = s.sequence.windows(block_size + 2).enumerate();
-    for (i, window) in iter {
-        let vi_prev = window[0];
-        let vi = window[1];
-        let vi_next = window[block_size + 1];
-        let block_end = window[block_size];
+    'scan: for vi in scan.nodes(tour) {
+        // The block can't contain node 0, which closes the sequence
+        let block_end = tour
+            .walk(vi)
+            .take(block_size)
+            .try_fold(vi, |_, v| (v != 0).then_some(v));
+        let Some(block_end) = block_end else {
+            continue;
+        };
+        let vi_prev = tour.prev(vi);
+        let vi_next = tour.next(block_end);
 
         let removal_delta = c(vi_prev, vi_next) - c(vi_prev, vi) - c(block_end, vi_next);
-        let mut check_delta = |j: usize, window_j: &[usize]| {
-            let vj = window_j[0];
-            let vj_next = window_j[1];
-
-            let delta = c(vj, vi) + c(block_end, vj_next) - c(vj, vj_next) + removal_delta;
-
-            if delta < best_delta {
-                best_delta = delta;
-                best_i = i + 1;
-                best_j = j;
+        let mut check_delta = |vj: usize, vj_next: usize| {
+            let insertion = if reversed {
+                c(vj, block_end) + c(vi, vj_next)
+            } else {
+                c(vj, vi) + c(block_end, vj_next)
+            };
+            let delta = insertion - c(vj, vj_next) + removal_delta;
+
+            if delta < best.0 {
+                best = (delta, [vi_prev, vi, block_end, vi_next, vj, vj_next]);
+                return true;
             }
+            false
         };
 
-        let inner_iter_before = s.sequence.windows(2).take(i).enumerate();
-        for (j_offset, window_j) in inner_iter_before {
-            let j = j_offset;
-            check_delta(j, window_j);
+        let before = edges(tour.walk(0).take_while(|&v| v != vi));
+        let after = edges(to_end(tour, vi_next));
+        for (vj, vj_next) in before.chain(after) {
+            if check_delta(vj, vj_next) && scan.first {
+                break 'scan;
+            }
         }
+    }
 
-        let inner_iter_after = s.sequence[i + block_size + 1..].windows(2).enumerate();
-        for (j_offset, window_j) in inner_iter_after {
-            let j = i + block_size + j_offset + 1;
-            check_delta(j, window_j);
-        }
+    best
+}
+
+fn apply_oropt(tour: &mut impl Tour, edges: [usize; 6], reversed: bool) {
+    let reconnection = if reversed {
+        Reconnection::ReverseFirst
+    } else {
+        Reconnection::Exchange
+    };
+    reconnection.apply(tour, edges);
+}
+
+fn best_oropt(
+    tour: &mut impl Tour,
+    scan: Scan,
+    block_size: usize,
+    reversed: bool,
+    instance: &Instance,
+) -> i32 {
+    let (delta, edges) = find_oropt(tour, scan, block_size, reversed, instance);
+    if delta < 0 {
+        apply_oropt(tour, edges, reversed);
     }
+    delta
+}
 
-    if best_delta < 0 {
-        if best_i < best_j {
-            s.sequence[best_i..=best_j].rotate_left(block_size);
-        } else {
-            s.sequence[(best_j + 1)..(best_i + block_size)].rotate_right(block_size);
+// Or-opt over every block size up to the maximum, each move is still evaluated in O(1)
+// First improvement stops at the first block size with an improving move
+fn best_segment_insertion(
+    tour: &mut impl Tour,
+    scan: Scan,
+    config: SegmentInsertion,
+    instance: &Instance,
+) -> i32 {
+    let max_size = config.max_size.min(instance.dimension - 1);
+    let orientations: &[bool] = if config.reversed {
+        &[false, true]
+    } else {
+        &[false]
+    };
+
+    let mut moves = (1..=max_size)
+        .flat_map(|block_size| {
+            orientations
+                .iter()
+                .map(move |&reversed| (block_size, reversed))
+        })
+        .map(|(block_size, reversed)| {
+            let best = find_oropt(tour, scan, block_size, reversed, instance);
+            (best, reversed)
+        });
+    let best = if scan.first {
+        moves.find(|(best, _)| best.0 < 0)
+    } else {
+        moves.min_by_key(|(best, _)| best.0)
+    };
+
+    match best {
+        Some(((delta, edges), reversed)) if delta < 0 => {
+            apply_oropt(tour, edges, reversed);
+            delta
         }
+        _ => 0,
+    }
+}
 
-        s.value = (s.value as i32 + best_delta) as u32;
+// Applies the move chosen by the neighbourhood and returns its delta
+fn improve(
+    search_type: &Searches,
+    tour: &mut impl Tour,
+    scan: Scan,
+    three_opt: Option<&[Vec<usize>]>,
+    instance: &Instance,
+) -> i32 {
+    use Searches::*;
+    match search_type {
+        Swap => best_swap(tour, scan, instance),
+        TwoOpt => best_2opt(tour, scan, instance),
+        OrOpt(block_size) => best_oropt(tour, scan, *block_size, false, instance),
+        ReversedOrOpt(block_size) => best_oropt(tour, scan, *block_size, true, instance),
+        SegmentInsertion(config) => best_segment_insertion(tour, scan, *config, instance),
+        ThreeOpt => best_3opt(tour, scan, three_opt.unwrap(), instance),
+    }
+}
 
-        return true;
+// Random variable neighbourhood descent, the moves are applied on a tour that is
+// a two-level list on large instances, so they don't reverse O(n) nodes
+pub fn local_search(
+    s: &mut Solution,
+    instance: &Instance,
+    segment_insertion: Option<SegmentInsertion>,
+    three_opt: Option<&[Vec<usize>]>,
+    improvement: Improvement,
+    rng: &mut impl Rng,
+) {
+    if instance.dimension < TWO_LEVEL_DIMENSION {
+        let tour = ArrayTour::new(&s.sequence, instance.dimension);
+        rvnd(
+            s,
+            tour,
+            instance,
+            segment_insertion,
+            three_opt,
+            improvement,
+            rng,
+        );
+    } else {
+        let tour = TwoLevelTour::new(&s.sequence, instance.dimension);
+        rvnd(
+            s,
+            tour,
+            instance,
+            segment_insertion,
+            three_opt,
+            improvement,
+            rng,
+        );
     }
 
-    false
+    debug_assert_eq!(
+        s.value,
+        {
+            let mut check = s.clone();
+            check.recalculate(instance);
+            check.value
+        },
+        "the deltas of the moves don't match the cost of the sequence"
+    );
 }
 
-pub fn local_search(s: &mut Solution, instance: &Instance) {
+fn rvnd(
+    s: &mut Solution,
+    mut tour: impl Tour,
+    instance: &Instance,
+    segment_insertion: Option<SegmentInsertion>,
+    three_opt: Option<&[Vec<usize>]>,
+    improvement: Improvement,
+    rng: &mut impl Rng,
+) {
     use Searches::*;
-    const SEARCHES: [Searches; 5] = [Swap, TwoOpt, OrOpt(1), OrOpt(2), OrOpt(3)];
-
-    let mut nl = SEARCHES.to_vec();
+    const SEARCHES: [Searches; 7] = [
+        Swap,
+        TwoOpt,
+        OrOpt(1),
+        OrOpt(2),
+        OrOpt(3),
+        ReversedOrOpt(2),
+        ReversedOrOpt(3),
+    ];
+
+    let searches: Vec<Searches> = SEARCHES
+        .into_iter()
+        .chain(segment_insertion.map(SegmentInsertion))
+        .chain(three_opt.map(|_| ThreeOpt))
+        .collect();
+    let mut nl = searches.clone();
 
     while !nl.is_empty() {
-        let chosen = rng().random_range(0..nl.len());
+        let chosen = rng.random_range(0..nl.len());
         let search_type = &nl[chosen];
+        let scan = Scan::new(improvement, rng);
 
-        let improved = match search_type {
-            Swap => best_swap(s, instance),
-            TwoOpt => best_2opt(s, instance),
-            OrOpt(block_size) => best_oropt(s, *block_size, instance),
-        };
+        let delta = improve(search_type, &mut tour, scan, three_opt, instance);
 
-        if improved {
-            nl = SEARCHES.to_vec();
+        if delta < 0 {
+            s.value = (s.value as i32 + delta) as u32;
+            nl = searches.clone();
         } else {
             nl.swap_remove(chosen);
         }
     }
+
+    s.sequence = tour.sequence();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn random_solution(instance: &Instance, rng: &mut StdRng) -> Solution {
+        let mut sequence: Vec<usize> = (1..instance.dimension).collect();
+        sequence.shuffle(rng);
+        sequence.insert(0, 0);
+        sequence.push(0);
+
+        let mut s = Solution { sequence, value: 0 };
+        s.recalculate(instance);
+        s
+    }
+
+    // Checks the tour against the value it should have after the move
+    fn assert_value(tour: &impl Tour, value: i32, instance: &Instance) {
+        let n = instance.dimension;
+        let mut s = Solution {
+            sequence: tour.sequence(),
+            value: 0,
+        };
+        assert_eq!((s.sequence[0], s.sequence[n]), (0, 0));
+        let mut nodes = s.sequence[..n].to_vec();
+        nodes.sort_unstable();
+        assert!(nodes.into_iter().eq(0..n), "not a permutation");
+
+        s.recalculate(instance);
+        assert_eq!(s.value as i32, value);
+    }
+
+    // A random solution always has an improving move in every neighbourhood
+    #[test]
+    fn first_improvement_applies_improving_moves() {
+        use Searches::*;
+        let instance = instance_reader::read_data("instances/kroA100.tsp");
+        let n = instance.dimension;
+        let neighbours = instance.nearest_neighbours(10);
+        let mut rng = StdRng::seed_from_u64(0);
+        let config = super::SegmentInsertion {
+            max_size: 6,
+            reversed: true,
+        };
+
+        for _ in 0..10 {
+            for search_type in [
+                Swap,
+                TwoOpt,
+                OrOpt(1),
+                OrOpt(2),
+                OrOpt(3),
+                ReversedOrOpt(2),
+                ReversedOrOpt(3),
+                SegmentInsertion(config),
+                ThreeOpt,
+            ] {
+                let s = random_solution(&instance, &mut rng);
+                let scan = Scan::new(Improvement::First, &mut rng);
+                let mut array = ArrayTour::new(&s.sequence, n);
+                let mut two_level = TwoLevelTour::new(&s.sequence, n);
+
+                let delta = improve(&search_type, &mut array, scan, Some(&neighbours), &instance);
+                assert!(delta < 0, "{search_type:?}");
+                assert_value(&array, s.value as i32 + delta, &instance);
+
+                // Both tours start with the same orientation, so the scan finds the same move
+                let same = improve(
+                    &search_type,
+                    &mut two_level,
+                    scan,
+                    Some(&neighbours),
+                    &instance,
+                );
+                assert_eq!(same, delta, "{search_type:?}");
+                assert_value(&two_level, s.value as i32 + delta, &instance);
+            }
+        }
+    }
+
+    // Applies the neighbourhood until it stops improving, every delta must match the sequence
+    fn descend(
+        mut tour: impl Tour,
+        search_type: &Searches,
+        s: &Solution,
+        improvement: Improvement,
+        rng: &mut StdRng,
+        instance: &Instance,
+    ) {
+        let mut value = s.value as i32;
+        loop {
+            let scan = Scan::new(improvement, rng);
+            let delta = improve(search_type, &mut tour, scan, None, instance);
+            if delta == 0 {
+                return;
+            }
+            assert!(delta < 0, "{search_type:?}");
+            value += delta;
+            assert_value(&tour, value, instance);
+        }
+    }
+
+    #[test]
+    fn reversed_oropt_matches_recalculate() {
+        let instance = instance_reader::read_data("instances/gr17.tsp");
+        let n = instance.dimension;
+        let mut rng = StdRng::seed_from_u64(0);
+
+        for improvement in [Improvement::Best, Improvement::First] {
+            for block_size in 1..=3 {
+                let search_type = Searches::ReversedOrOpt(block_size);
+                for _ in 0..10 {
+                    let s = random_solution(&instance, &mut rng);
+                    let tour = ArrayTour::new(&s.sequence, n);
+                    descend(tour, &search_type, &s, improvement, &mut rng, &instance);
+                    let tour = TwoLevelTour::new(&s.sequence, n);
+                    descend(tour, &search_type, &s, improvement, &mut rng, &instance);
+                }
+            }
+        }
+    }
+
+    // Segment insertion picks among the block sizes, each of them is also checked on its own
+    #[test]
+    fn segment_insertion_matches_recalculate() {
+        let instance = instance_reader::read_data("instances/gr17.tsp");
+        let n = instance.dimension;
+        let mut rng = StdRng::seed_from_u64(0);
+
+        for improvement in [Improvement::Best, Improvement::First] {
+            for reversed in [false, true] {
+                let config = super::SegmentInsertion {
+                    max_size: 8,
+                    reversed,
+                };
+                let searches = (1..=config.max_size).map(|block_size| match reversed {
+                    false => Searches::OrOpt(block_size),
+                    true => Searches::ReversedOrOpt(block_size),
+                });
+
+                for search_type in searches.chain([Searches::SegmentInsertion(config)]) {
+                    for _ in 0..10 {
+                        let s = random_solution(&instance, &mut rng);
+                        let tour = ArrayTour::new(&s.sequence, n);
+                        descend(tour, &search_type, &s, improvement, &mut rng, &instance);
+                        let tour = TwoLevelTour::new(&s.sequence, n);
+                        descend(tour, &search_type, &s, improvement, &mut rng, &instance);
+                    }
+                }
+            }
+        }
+    }
 }
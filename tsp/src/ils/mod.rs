@@ -1,40 +1,239 @@
+mod candidate_search;
 mod construction;
+mod lin_kernighan;
 mod local_search;
 mod perturbation;
+mod three_opt;
+mod tour;
 
 use crate::solution::Solution;
+pub use construction::Construction;
 use construction::construction;
+pub use grasp::Alpha;
+use grasp::GraspAlpha;
 use instance_reader::Instance;
 use local_search::local_search;
+pub use local_search::{Improvement, SegmentInsertion};
 use perturbation::perturbation;
+use rand::Rng;
+use rand::SeedableRng;
+use rand::rngs::StdRng;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::thread;
+use std::time::{Duration, Instant};
 
-pub fn ils(max_iter: u32, max_iter_ils: u32, instance: &Instance) -> Solution {
-    let mut best = Solution {
-        value: u32::MAX,
-        ..Default::default()
-    };
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LocalSearch {
+    // Random variable neighbourhood descent over the whole neighbourhoods
+    Rvnd,
+    // Moves towards the nearest neighbours of each node with don't look bits
+    Candidates,
+    // Chains of 2-opt moves between nearest neighbours
+    LinKernighan,
+}
 
-    for _ in 0..max_iter {
-        let mut s = construction(instance);
-        let mut inner_best = s.clone();
+#[derive(Debug, Clone)]
+pub struct IlsConfig {
+    // Number of restarts
+    pub max_iter: u32,
+    // Perturbations without improvement before restarting
+    pub max_iter_ils: u32,
+    // The search stops between local searches once the limit or the target is reached
+    pub time_limit: Option<Duration>,
+    pub target: Option<u32>,
+    // Restarts run in parallel on this many threads
+    pub threads: usize,
+    pub local_search: LocalSearch,
+    // Size of the neighbour lists used by the candidate search, Lin-Kernighan and 3-opt
+    pub neighbours: usize,
+    // Adds or-opt with longer blocks to the local search
+    pub segment_insertion: Option<SegmentInsertion>,
+    // Adds 3-opt between neighbouring nodes to the RVND
+    pub three_opt: bool,
+    // Whether the local search applies the best or the first improving move it finds
+    pub improvement: Improvement,
+    // Heuristic that builds the solution of each restart
+    pub construction: Construction,
+    // Size of the restricted candidate list of the GRASP construction
+    pub alpha: Alpha,
+}
 
-        let mut iter_ils = 1;
-        while iter_ils <= max_iter_ils {
-            local_search(&mut s, instance);
+impl IlsConfig {
+    pub fn new(max_iter: u32, max_iter_ils: u32) -> Self {
+        IlsConfig {
+            max_iter,
+            max_iter_ils,
+            time_limit: None,
+            target: None,
+            threads: 1,
+            local_search: LocalSearch::Rvnd,
+            neighbours: 10,
+            segment_insertion: None,
+            three_opt: false,
+            improvement: Improvement::Best,
+            construction: Construction::Grasp,
+            alpha: Alpha::Random,
+        }
+    }
+}
+
+// One restart: construction followed by perturbations until max_iter_ils fail to improve
+fn restart(
+    config: &IlsConfig,
+    instance: &Instance,
+    neighbours: Option<&[Vec<usize>]>,
+    alpha: Option<f64>,
+    rng: &mut impl Rng,
+    should_stop: impl Fn(&Solution) -> bool,
+) -> Solution {
+    let mut s = construction(config.construction, alpha, instance, rng);
+    let mut inner_best = s.clone();
 
-            if s.value < inner_best.value {
-                inner_best = s.clone();
-                iter_ils = 0;
+    let mut iter_ils = 1;
+    while iter_ils <= config.max_iter_ils {
+        match (config.local_search, neighbours) {
+            (LocalSearch::Candidates, Some(neighbours)) => candidate_search::local_search(
+                &mut s,
+                instance,
+                neighbours,
+                config.improvement,
+                rng,
+            ),
+            (LocalSearch::LinKernighan, Some(neighbours)) => {
+                lin_kernighan::local_search(&mut s, instance, neighbours, config.improvement, rng)
             }
+            _ => local_search(
+                &mut s,
+                instance,
+                config.segment_insertion,
+                neighbours.filter(|_| config.three_opt),
+                config.improvement,
+                rng,
+            ),
+        }
 
-            s = perturbation(inner_best.clone(), instance);
-            iter_ils += 1;
+        if s.value < inner_best.value {
+            inner_best = s.clone();
+            iter_ils = 0;
         }
 
-        if inner_best.value < best.value {
-            best = inner_best;
+        if should_stop(&inner_best) {
+            break;
         }
+
+        s = perturbation(inner_best.clone(), instance, rng);
+        iter_ils += 1;
     }
 
-    best
+    inner_best
+}
+
+// Restarts run on a pool of threads, each one with a generator seeded from rng
+// The result only depends on rng unless the search is stopped by the time limit or the target
+pub fn ils(config: &IlsConfig, instance: &Instance, rng: &mut impl Rng) -> Solution {
+    let deadline = config.time_limit.map(|limit| Instant::now() + limit);
+    let stop = AtomicBool::new(false);
+    let should_stop = |best: &Solution| {
+        if deadline.is_some_and(|deadline| Instant::now() >= deadline)
+            || config.target.is_some_and(|target| best.value <= target)
+        {
+            stop.store(true, Ordering::Relaxed);
+        }
+        stop.load(Ordering::Relaxed)
+    };
+
+    let neighbours = (config.local_search != LocalSearch::Rvnd || config.three_opt)
+        .then(|| instance.nearest_neighbours(config.neighbours));
+    // The first restart always runs, so there is a solution even when the search stops at once
+    let seeds: Vec<u64> = (0..config.max_iter.max(1)).map(|_| rng.random()).collect();
+    let grasp_alpha = GraspAlpha::new(&config.alpha, seeds.len());
+    let next_start = AtomicUsize::new(0);
+
+    let worker = || {
+        let mut found = Vec::new();
+        loop {
+            let start = next_start.fetch_add(1, Ordering::Relaxed);
+            if start >= seeds.len() {
+                return found;
+            }
+            if stop.load(Ordering::Relaxed) {
+                grasp_alpha.skip(start);
+                return found;
+            }
+
+            let mut rng = StdRng::seed_from_u64(seeds[start]);
+            let (alpha, alpha_index) = grasp_alpha.choose(start, &mut rng);
+            let s = restart(
+                config,
+                instance,
+                neighbours.as_deref(),
+                alpha,
+                &mut rng,
+                should_stop,
+            );
+            grasp_alpha.report(start, alpha_index, s.value);
+            found.push((start, s));
+        }
+    };
+
+    let best = thread::scope(|scope| {
+        let workers: Vec<_> = (0..config.threads.max(1))
+            .map(|_| scope.spawn(worker))
+            .collect();
+
+        workers
+            .into_iter()
+            .flat_map(|worker| worker.join().unwrap())
+            .min_by_key(|(start, s)| (s.value, *start))
+    });
+
+    best.map(|(_, s)| s)
+        .expect("The first restart always finishes")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::exact::held_karp;
+
+    fn assert_valid(s: &Solution, instance: &Instance) {
+        let n = instance.dimension;
+        assert_eq!(s.sequence.len(), n + 1);
+        assert_eq!((s.sequence[0], s.sequence[n]), (0, 0));
+        let mut nodes = s.sequence[..n].to_vec();
+        nodes.sort_unstable();
+        assert!(nodes.into_iter().eq(0..n), "not a permutation");
+
+        let mut check = s.clone();
+        check.recalculate(instance);
+        assert_eq!(check.value, s.value);
+    }
+
+    #[test]
+    fn ils_finds_the_optimum() {
+        for local_search in [
+            LocalSearch::Rvnd,
+            LocalSearch::Candidates,
+            LocalSearch::LinKernighan,
+        ] {
+            for improvement in [Improvement::Best, Improvement::First] {
+                for name in ["burma14", "ulysses16", "gr17"] {
+                    let instance = instance_reader::read_data(&format!("instances/{name}.tsp"));
+                    let optimum = held_karp(&instance).unwrap();
+                    let config = IlsConfig {
+                        local_search,
+                        improvement,
+                        ..IlsConfig::new(10, instance.dimension as u32)
+                    };
+
+                    let s = ils(&config, &instance, &mut StdRng::seed_from_u64(0));
+                    assert_valid(&s, &instance);
+                    assert_eq!(
+                        s.value, optimum.value,
+                        "{name} with {local_search:?} and {improvement:?}"
+                    );
+                }
+            }
+        }
+    }
 }
@@ -0,0 +1,245 @@
+use instance_reader::Instance;
+
+use super::local_search::Scan;
+use super::tour::Tour;
+use Reconnection::*;
+
+// Pure 3-opt reconnections after removing the edges (a, b), (c, d) and (e, f),
+// which follow the tour in this order and split it as A B C D
+// with B going from b to c and C from d to e
+#[derive(Debug, Clone, Copy)]
+pub enum Reconnection {
+    // A C B D
+    Exchange,
+    // A C B' D
+    ReverseFirst,
+    // A C' B D
+    ReverseSecond,
+    // A B' C' D
+    ReverseBoth,
+}
+
+impl Reconnection {
+    pub fn delta(self, [a, b, c_, d, e, f]: [usize; 6], instance: &Instance) -> i32 {
+        let c = |i: usize, j: usize| instance.distance(i, j) as i32;
+
+        let added = match self {
+            Exchange => c(a, d) + c(e, b) + c(c_, f),
+            ReverseFirst => c(a, d) + c(e, c_) + c(b, f),
+            ReverseSecond => c(a, e) + c(d, b) + c(c_, f),
+            ReverseBoth => c(a, c_) + c(b, e) + c(d, f),
+        };
+        added - c(a, b) - c(c_, d) - c(e, f)
+    }
+
+    // Applied as 2-opt moves, so it doesn't depend on the orientation of the tour
+    pub fn apply(self, tour: &mut impl Tour, [a, b, c, d, e, f]: [usize; 6]) {
+        match self {
+            // A C B' D, then B is turned back
+            Exchange => {
+                ReverseFirst.apply(tour, [a, b, c, d, e, f]);
+                tour.move_2opt(e, c, f, b);
+            }
+            // A C' B' D, then C is turned back
+            ReverseFirst => {
+                tour.move_2opt(a, b, f, e);
+                tour.move_2opt(a, e, c, d);
+            }
+            // A C' B' D, then B is turned back
+            ReverseSecond => {
+                tour.move_2opt(a, b, f, e);
+                tour.move_2opt(d, c, f, b);
+            }
+            // A B' C D, then C is turned too
+            ReverseBoth => {
+                tour.move_2opt(a, b, d, c);
+                tour.move_2opt(b, d, f, e);
+            }
+        }
+    }
+}
+
+// Best 3-opt move restricted by neighbour lists
+// The first new edge always connects the start of the first removed edge to one of its neighbours,
+// and the second one connects the end of that edge to one of its own neighbours
+// First improvement stops after the first start with an improving move
+pub fn best_3opt(
+    tour: &mut impl Tour,
+    scan: Scan,
+    neighbours: &[Vec<usize>],
+    instance: &Instance,
+) -> i32 {
+    let c = |i: usize, j: usize| instance.distance(i, j) as i32;
+
+    let mut best = (0, [0; 6], Exchange);
+
+    for a in scan.nodes(tour) {
+        let b = tour.next(a);
+
+        // The removed edges are named by the node they leave,
+        // they must come after one another in the sequence that starts at node 0
+        let mut check = |q: usize, r: usize, reconnection: Reconnection| {
+            if q == a || r == q || !tour.between(0, a, q) || !tour.between(0, q, r) {
+                return;
+            }
+
+            let edges = [a, b, q, tour.next(q), r, tour.next(r)];
+            let delta = reconnection.delta(edges, instance);
+            if delta < best.0 {
+                best = (delta, edges, reconnection);
+            }
+        };
+
+        for &x in neighbours[a].iter().filter(|&&x| c(a, x) < c(a, b)) {
+            for &y in &neighbours[b] {
+                // a -> d with d = x, e -> b with e = y
+                check(tour.prev(x), y, Exchange);
+                // a -> d with d = x, b -> f with f = y
+                check(tour.prev(x), tour.prev(y), ReverseFirst);
+                // a -> e with e = x, d -> b with d = y
+                check(tour.prev(y), x, ReverseSecond);
+                // a -> c with c = x, b -> e with e = y
+                check(x, y, ReverseBoth);
+            }
+        }
+
+        if scan.first && best.0 < 0 {
+            break;
+        }
+    }
+
+    let (delta, edges, reconnection) = best;
+    if delta < 0 {
+        reconnection.apply(tour, edges);
+    }
+    delta
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ils::Improvement;
+    use crate::ils::tour::{ArrayTour, TwoLevelTour};
+    use crate::solution::Solution;
+    use rand::SeedableRng;
+    use rand::rngs::StdRng;
+    use rand::seq::SliceRandom;
+
+    fn random_solution(instance: &Instance, rng: &mut StdRng) -> Solution {
+        let mut sequence: Vec<usize> = (1..instance.dimension).collect();
+        sequence.shuffle(rng);
+        sequence.insert(0, 0);
+        sequence.push(0);
+
+        let mut s = Solution { sequence, value: 0 };
+        s.recalculate(instance);
+        s
+    }
+
+    fn assert_valid(s: &Solution, instance: &Instance) {
+        let n = instance.dimension;
+        assert_eq!((s.sequence[0], s.sequence[n]), (0, 0));
+        let mut nodes = s.sequence[..n].to_vec();
+        nodes.sort_unstable();
+        assert!(nodes.into_iter().eq(0..n), "not a permutation");
+    }
+
+    // Solution after applying the reconnection on the tour
+    fn applied(
+        mut tour: impl Tour,
+        reconnection: Reconnection,
+        edges: [usize; 6],
+        instance: &Instance,
+    ) -> Solution {
+        reconnection.apply(&mut tour, edges);
+        let mut s = Solution {
+            sequence: tour.sequence(),
+            value: 0,
+        };
+        s.recalculate(instance);
+        s
+    }
+
+    // Every p < q < r, so the first removed edge leaves the depot when p = 0
+    // and the last one goes back to it when r = n - 1
+    #[test]
+    fn reconnections_match_recalculate() {
+        let instance = instance_reader::read_data("instances/burma14.tsp");
+        let n = instance.dimension;
+        let mut rng = StdRng::seed_from_u64(0);
+
+        for _ in 0..5 {
+            let s = random_solution(&instance, &mut rng);
+            let seq = &s.sequence;
+            for reconnection in [Exchange, ReverseFirst, ReverseSecond, ReverseBoth] {
+                for p in 0..n {
+                    for q in p + 1..n {
+                        for r in q + 1..n {
+                            let edges =
+                                [seq[p], seq[p + 1], seq[q], seq[q + 1], seq[r], seq[r + 1]];
+                            let delta = reconnection.delta(edges, &instance);
+                            for moved in [
+                                applied(ArrayTour::new(seq, n), reconnection, edges, &instance),
+                                applied(TwoLevelTour::new(seq, n), reconnection, edges, &instance),
+                            ] {
+                                assert_valid(&moved, &instance);
+                                assert_eq!(
+                                    moved.value as i32,
+                                    s.value as i32 + delta,
+                                    "{reconnection:?} at {p} {q} {r}"
+                                );
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    // Applies 3-opt moves until none improves, the deltas must match the sequence
+    fn descend(
+        mut tour: impl Tour,
+        s: &Solution,
+        neighbours: &[Vec<usize>],
+        improvement: Improvement,
+        rng: &mut StdRng,
+        instance: &Instance,
+    ) {
+        let mut value = s.value as i32;
+        loop {
+            let scan = Scan::new(improvement, rng);
+            let delta = best_3opt(&mut tour, scan, neighbours, instance);
+            if delta == 0 {
+                return;
+            }
+            assert!(delta < 0);
+            value += delta;
+
+            let mut moved = Solution {
+                sequence: tour.sequence(),
+                value: 0,
+            };
+            assert_valid(&moved, instance);
+            moved.recalculate(instance);
+            assert_eq!(moved.value as i32, value);
+        }
+    }
+
+    #[test]
+    fn best_3opt_matches_recalculate() {
+        let instance = instance_reader::read_data("instances/gr17.tsp");
+        let n = instance.dimension;
+        let neighbours = instance.nearest_neighbours(8);
+        let mut rng = StdRng::seed_from_u64(0);
+
+        for improvement in [Improvement::Best, Improvement::First] {
+            for _ in 0..20 {
+                let s = random_solution(&instance, &mut rng);
+                let tour = ArrayTour::new(&s.sequence, n);
+                descend(tour, &s, &neighbours, improvement, &mut rng, &instance);
+                let tour = TwoLevelTour::new(&s.sequence, n);
+                descend(tour, &s, &neighbours, improvement, &mut rng, &instance);
+            }
+        }
+    }
+}
@@ -0,0 +1,72 @@
+use super::Tour;
+
+// Tour stored as an array with the position of every node, flips take O(n)
+pub struct ArrayTour {
+    order: Vec<usize>,
+    pos: Vec<usize>,
+}
+
+impl ArrayTour {
+    pub fn new(sequence: &[usize], dimension: usize) -> Self {
+        let order = sequence[..sequence.len() - 1].to_vec();
+        let mut pos = vec![0; dimension];
+        for (p, &v) in order.iter().enumerate() {
+            pos[v] = p;
+        }
+        ArrayTour { order, pos }
+    }
+}
+
+impl Tour for ArrayTour {
+    fn next(&self, a: usize) -> usize {
+        match self.order.get(self.pos[a] + 1) {
+            Some(&next) => next,
+            None => self.order[0],
+        }
+    }
+
+    fn prev(&self, a: usize) -> usize {
+        match self.pos[a] {
+            0 => self.order[self.order.len() - 1],
+            p => self.order[p - 1],
+        }
+    }
+
+    fn between(&self, a: usize, b: usize, c: usize) -> bool {
+        let n = self.order.len();
+        let from_a = |v: usize| (self.pos[v] + n - self.pos[a]) % n;
+        from_a(b) <= from_a(c)
+    }
+
+    // The complement is reversed instead when it is shorter, which gives the same cycle
+    fn flip(&mut self, a: usize, b: usize) {
+        let n = self.order.len();
+        let len = (self.pos[b] + n - self.pos[a]) % n + 1;
+        let (mut i, mut j, len) = if 2 * len > n {
+            (self.pos[b] + 1, self.pos[a] + n - 1, n - len)
+        } else {
+            (self.pos[a], self.pos[b], len)
+        };
+
+        for _ in 0..len / 2 {
+            let (u, v) = (self.order[i % n], self.order[j % n]);
+            self.order[i % n] = v;
+            self.order[j % n] = u;
+            self.pos[v] = i % n;
+            self.pos[u] = j % n;
+            i += 1;
+            j += n - 1;
+        }
+    }
+
+    fn walk(&self, a: usize) -> impl Iterator<Item = usize> + '_ {
+        let p = self.pos[a];
+        self.order[p..].iter().chain(&self.order[..p]).copied()
+    }
+
+    fn sequence(&self) -> Vec<usize> {
+        let start = self.pos[0];
+        let n = self.order.len();
+        (0..=n).map(|k| self.order[(start + k) % n]).collect()
+    }
+}
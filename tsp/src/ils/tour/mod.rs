@@ -0,0 +1,60 @@
+mod array;
+mod two_level;
+
+pub use array::ArrayTour;
+pub use two_level::TwoLevelTour;
+
+use std::iter;
+
+// Smallest dimension where the two-level list flips faster than the array
+pub const TWO_LEVEL_DIMENSION: usize = 5000;
+
+// Cyclic tour with an orientation, the searches ask for neighbours and reverse paths
+pub trait Tour {
+    fn next(&self, a: usize) -> usize;
+
+    fn prev(&self, a: usize) -> usize;
+
+    // Whether b is on the path that goes forward from a to c
+    fn between(&self, a: usize, b: usize, c: usize) -> bool;
+
+    // Reverses the path that goes forward from a to b
+    // The orientation of the tour may change, only the cycle is kept
+    fn flip(&mut self, a: usize, b: usize);
+
+    // Sequence starting and ending at node 0
+    fn sequence(&self) -> Vec<usize>;
+
+    // Nodes going forward from a, once around the tour
+    fn walk(&self, a: usize) -> impl Iterator<Item = usize> + '_ {
+        iter::successors(Some(a), move |&v| {
+            Some(self.next(v)).filter(|&next| next != a)
+        })
+    }
+
+    // Replaces the edges (t1, t2) and (t4, t3) by (t2, t3) and (t4, t1),
+    // where t1 -> t2 and t4 -> t3 go in the same direction
+    fn move_2opt(&mut self, t1: usize, t2: usize, t3: usize, t4: usize) {
+        if self.next(t1) == t2 {
+            debug_assert!(self.next(t4) == t3 && !self.between(t2, t1, t4));
+            self.flip(t2, t4);
+        } else {
+            debug_assert!(self.prev(t4) == t3 && !self.between(t4, t1, t2));
+            self.flip(t4, t2);
+        }
+    }
+
+    // Exchanges the places of a and b with at most two 2-opt moves
+    fn swap(&mut self, a: usize, b: usize) {
+        if self.next(b) == a {
+            return self.swap(b, a);
+        }
+
+        // pa a na ... pb b nb becomes pa b pb ... na a nb, then pa b na ... pb a nb
+        let (pa, na, pb, nb) = (self.prev(a), self.next(a), self.prev(b), self.next(b));
+        self.move_2opt(pa, a, nb, b);
+        if na != b {
+            self.move_2opt(b, pb, a, na);
+        }
+    }
+}
@@ -0,0 +1,553 @@
+use super::Tour;
+
+const NONE: usize = usize::MAX;
+
+// The segments are rebuilt once one of them grows past this many times the target size
+const MAX_GROWTH: usize = 4;
+
+// Two-level doubly linked list, flips take O(√n)
+// The tour is split in about √n segments kept in a cyclic array, each one a doubly linked list
+// of nodes numbered in its own direction, with a bit telling whether the tour goes through it
+// backwards. A flip moves nodes between neighbouring segments until the path starts and ends
+// at segment boundaries, then reverses the order and the bits of the segments in between
+pub struct TwoLevelTour {
+    segment: Vec<usize>,
+    id: Vec<i64>,
+    next_in: Vec<usize>,
+    prev_in: Vec<usize>,
+    // first and last follow the direction of the segment
+    first: Vec<usize>,
+    last: Vec<usize>,
+    reversed: Vec<bool>,
+    // Position of each segment in order
+    pos: Vec<usize>,
+    order: Vec<usize>,
+    target: usize,
+}
+
+impl TwoLevelTour {
+    pub fn new(sequence: &[usize], dimension: usize) -> Self {
+        let mut tour = TwoLevelTour {
+            segment: vec![0; dimension],
+            id: vec![0; dimension],
+            next_in: vec![NONE; dimension],
+            prev_in: vec![NONE; dimension],
+            first: Vec::new(),
+            last: Vec::new(),
+            reversed: Vec::new(),
+            pos: Vec::new(),
+            order: Vec::new(),
+            target: 1,
+        };
+        tour.rebuild(&sequence[..sequence.len() - 1]);
+        tour
+    }
+
+    // Splits the nodes in segments of the target size, at least 3 of them
+    fn rebuild(&mut self, nodes: &[usize]) {
+        let n = nodes.len();
+        self.target = (n as f64).sqrt().ceil() as usize;
+        self.target = self.target.min(n.div_ceil(3)).max(1);
+
+        self.first.clear();
+        self.last.clear();
+        for (k, chunk) in nodes.chunks(self.target).enumerate() {
+            for (i, &v) in chunk.iter().enumerate() {
+                self.segment[v] = k;
+                self.id[v] = i as i64;
+                self.prev_in[v] = if i == 0 { NONE } else { chunk[i - 1] };
+                self.next_in[v] = chunk.get(i + 1).copied().unwrap_or(NONE);
+            }
+            self.first.push(chunk[0]);
+            self.last.push(chunk[chunk.len() - 1]);
+        }
+
+        let m = self.first.len();
+        self.reversed = vec![false; m];
+        self.pos = (0..m).collect();
+        self.order = (0..m).collect();
+    }
+
+    // Nodes in tour order
+    fn nodes(&self) -> Vec<usize> {
+        let mut v = self.head(self.order[0]);
+        (0..self.segment.len())
+            .map(|_| {
+                let current = v;
+                v = self.next(v);
+                current
+            })
+            .collect()
+    }
+
+    fn head(&self, s: usize) -> usize {
+        if self.reversed[s] {
+            self.last[s]
+        } else {
+            self.first[s]
+        }
+    }
+
+    fn tail(&self, s: usize) -> usize {
+        if self.reversed[s] {
+            self.first[s]
+        } else {
+            self.last[s]
+        }
+    }
+
+    fn next_segment(&self, s: usize) -> usize {
+        self.order[(self.pos[s] + 1) % self.order.len()]
+    }
+
+    fn prev_segment(&self, s: usize) -> usize {
+        self.order[(self.pos[s] + self.order.len() - 1) % self.order.len()]
+    }
+
+    fn size(&self, s: usize) -> usize {
+        (self.id[self.last[s]] - self.id[self.first[s]] + 1) as usize
+    }
+
+    // Position of v inside its segment, following the tour
+    fn index(&self, v: usize) -> usize {
+        let s = self.segment[v];
+        if self.reversed[s] {
+            (self.id[self.last[s]] - self.id[v]) as usize
+        } else {
+            (self.id[v] - self.id[self.first[s]]) as usize
+        }
+    }
+
+    // Neighbours of v inside its segment following the tour, NONE at the ends
+    fn forward_in(&self, v: usize) -> usize {
+        if self.reversed[self.segment[v]] {
+            self.prev_in[v]
+        } else {
+            self.next_in[v]
+        }
+    }
+
+    fn backward_in(&self, v: usize) -> usize {
+        if self.reversed[self.segment[v]] {
+            self.next_in[v]
+        } else {
+            self.prev_in[v]
+        }
+    }
+
+    fn append(&mut self, s: usize, v: usize) {
+        self.segment[v] = s;
+        if self.reversed[s] {
+            let f = self.first[s];
+            self.prev_in[f] = v;
+            self.next_in[v] = f;
+            self.prev_in[v] = NONE;
+            self.id[v] = self.id[f] - 1;
+            self.first[s] = v;
+        } else {
+            let l = self.last[s];
+            self.next_in[l] = v;
+            self.prev_in[v] = l;
+            self.next_in[v] = NONE;
+            self.id[v] = self.id[l] + 1;
+            self.last[s] = v;
+        }
+    }
+
+    fn prepend(&mut self, s: usize, v: usize) {
+        self.segment[v] = s;
+        if self.reversed[s] {
+            let l = self.last[s];
+            self.next_in[l] = v;
+            self.prev_in[v] = l;
+            self.next_in[v] = NONE;
+            self.id[v] = self.id[l] + 1;
+            self.last[s] = v;
+        } else {
+            let f = self.first[s];
+            self.prev_in[f] = v;
+            self.next_in[v] = f;
+            self.prev_in[v] = NONE;
+            self.id[v] = self.id[f] - 1;
+            self.first[s] = v;
+        }
+    }
+
+    // Rebuilds the segments when s got too large, returns whether it did
+    fn rebalance(&mut self, s: usize) -> bool {
+        if self.size(s) <= MAX_GROWTH * self.target {
+            return false;
+        }
+        let nodes = self.nodes();
+        self.rebuild(&nodes);
+        true
+    }
+
+    // Moves the nodes from the head of s to v to the end of the previous segment
+    fn move_to_prev(&mut self, s: usize, v: usize) -> bool {
+        let t = self.prev_segment(s);
+        let new_head = self.forward_in(v);
+
+        let mut u = self.head(s);
+        loop {
+            let following = self.forward_in(u);
+            self.append(t, u);
+            if u == v {
+                break;
+            }
+            u = following;
+        }
+
+        if self.reversed[s] {
+            self.last[s] = new_head;
+            self.next_in[new_head] = NONE;
+        } else {
+            self.first[s] = new_head;
+            self.prev_in[new_head] = NONE;
+        }
+
+        self.rebalance(t)
+    }
+
+    // Moves the nodes from v to the tail of s to the start of the next segment
+    fn move_to_next(&mut self, s: usize, v: usize) -> bool {
+        let t = self.next_segment(s);
+        let new_tail = self.backward_in(v);
+
+        let mut u = self.tail(s);
+        loop {
+            let preceding = self.backward_in(u);
+            self.prepend(t, u);
+            if u == v {
+                break;
+            }
+            u = preceding;
+        }
+
+        if self.reversed[s] {
+            self.first[s] = new_tail;
+            self.prev_in[new_tail] = NONE;
+        } else {
+            self.last[s] = new_tail;
+            self.next_in[new_tail] = NONE;
+        }
+
+        self.rebalance(t)
+    }
+
+    // Makes a the head of a segment by moving the smaller side to a neighbouring segment
+    fn split_before(&mut self, a: usize) -> bool {
+        let s = self.segment[a];
+        let before = self.index(a);
+        if before <= self.size(s) - before {
+            self.move_to_prev(s, self.backward_in(a))
+        } else {
+            self.move_to_next(s, a)
+        }
+    }
+
+    // Makes b the tail of a segment by moving the smaller side to a neighbouring segment
+    fn split_after(&mut self, b: usize) -> bool {
+        let s = self.segment[b];
+        let after = self.size(s) - self.index(b) - 1;
+        if after <= self.index(b) + 1 {
+            self.move_to_next(s, self.forward_in(b))
+        } else {
+            self.move_to_prev(s, b)
+        }
+    }
+
+    // Reverses the path from a to b inside one segment
+    fn reverse_inside(&mut self, s: usize, a: usize, b: usize) {
+        let (p, q) = if self.reversed[s] { (b, a) } else { (a, b) };
+        let before = self.prev_in[p];
+        let after = self.next_in[q];
+
+        let mut nodes = vec![p];
+        while *nodes.last().unwrap() != q {
+            nodes.push(self.next_in[*nodes.last().unwrap()]);
+        }
+        nodes.reverse();
+
+        let base = self.id[p];
+        for (k, &v) in nodes.iter().enumerate() {
+            self.id[v] = base + k as i64;
+            self.prev_in[v] = if k == 0 { before } else { nodes[k - 1] };
+            self.next_in[v] = nodes.get(k + 1).copied().unwrap_or(after);
+        }
+
+        let (new_p, new_q) = (nodes[0], nodes[nodes.len() - 1]);
+        if before == NONE {
+            self.first[s] = new_p;
+        } else {
+            self.next_in[before] = new_p;
+        }
+        if after == NONE {
+            self.last[s] = new_q;
+        } else {
+            self.prev_in[after] = new_q;
+        }
+    }
+
+    // Reverses the segments from sa to sb, the path starts at the head of sa and ends at the tail of sb
+    fn reverse_segments(&mut self, sa: usize, sb: usize) {
+        let m = self.order.len();
+        let (mut i, mut j) = (self.pos[sa], self.pos[sb]);
+        let len = (j + m - i) % m + 1;
+
+        for k in 0..len {
+            let s = self.order[(i + k) % m];
+            self.reversed[s] = !self.reversed[s];
+        }
+        for _ in 0..len / 2 {
+            let (u, v) = (self.order[i % m], self.order[j % m]);
+            self.order[i % m] = v;
+            self.order[j % m] = u;
+            self.pos[v] = i % m;
+            self.pos[u] = j % m;
+            i += 1;
+            j += m - 1;
+        }
+    }
+}
+
+impl Tour for TwoLevelTour {
+    fn next(&self, a: usize) -> usize {
+        let s = self.segment[a];
+        if a == self.tail(s) {
+            self.head(self.next_segment(s))
+        } else {
+            self.forward_in(a)
+        }
+    }
+
+    fn prev(&self, a: usize) -> usize {
+        let s = self.segment[a];
+        if a == self.head(s) {
+            self.tail(self.prev_segment(s))
+        } else {
+            self.backward_in(a)
+        }
+    }
+
+    fn between(&self, a: usize, b: usize, c: usize) -> bool {
+        let key = |v: usize| (self.pos[self.segment[v]], self.index(v));
+        let (a, b, c) = (key(a), key(b), key(c));
+        if a <= c {
+            a <= b && b <= c
+        } else {
+            a <= b || b <= c
+        }
+    }
+
+    fn flip(&mut self, a: usize, b: usize) {
+        if a == b || self.next(b) == a {
+            return;
+        }
+
+        // The complement is reversed instead when the path spans more than half the segments,
+        // which also keeps the splits below from moving nodes into the other end of the path
+        let m = self.order.len();
+        let spanned = (self.pos[self.segment[b]] + m - self.pos[self.segment[a]]) % m + 1;
+        let (a, b) = if 2 * spanned > m + 1 {
+            (self.next(b), self.prev(a))
+        } else {
+            (a, b)
+        };
+
+        loop {
+            let (sa, sb) = (self.segment[a], self.segment[b]);
+            if sa == sb {
+                if self.index(a) <= self.index(b) {
+                    self.reverse_inside(sa, a, b);
+                } else {
+                    // The path goes around the tour, its complement is inside the segment
+                    let (from, to) = (self.next(b), self.prev(a));
+                    self.reverse_inside(sa, from, to);
+                }
+                return;
+            }
+
+            let rebuilt = if a != self.head(sa) {
+                self.split_before(a)
+            } else if b != self.tail(sb) {
+                self.split_after(b)
+            } else {
+                self.reverse_segments(sa, sb);
+                return;
+            };
+
+            if rebuilt {
+                return self.flip(a, b);
+            }
+        }
+    }
+
+    fn sequence(&self) -> Vec<usize> {
+        let mut sequence = vec![0];
+        let mut v = self.next(0);
+        while v != 0 {
+            sequence.push(v);
+            v = self.next(v);
+        }
+        sequence.push(0);
+        sequence
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::ArrayTour;
+    use super::*;
+    use rand::rngs::StdRng;
+    use rand::seq::SliceRandom;
+    use rand::{Rng, SeedableRng};
+
+    // Cycle reversed without the shortcuts of the tours, the path always goes from a to b
+    fn flip_reference(order: &mut [usize], a: usize, b: usize) {
+        let n = order.len();
+        let pos = |v: usize| order.iter().position(|&u| u == v).unwrap();
+        let (mut i, mut j) = (pos(a), pos(b));
+        let len = (j + n - i) % n + 1;
+        for _ in 0..len / 2 {
+            order.swap(i % n, j % n);
+            i += 1;
+            j += n - 1;
+        }
+    }
+
+    // Compares the tour with the reference cycle, the orientation may be the opposite one
+    // Returns whether the tour goes in the same direction as the reference
+    fn assert_same_cycle(tour: &impl Tour, order: &[usize], rng: &mut StdRng) -> bool {
+        let n = order.len();
+        let forward = tour.next(order[0]) == order[1];
+        let next = |i: usize| order[(i + 1) % n];
+        let prev = |i: usize| order[(i + n - 1) % n];
+
+        for (i, &v) in order.iter().enumerate() {
+            let (after, before) = if forward {
+                (next(i), prev(i))
+            } else {
+                (prev(i), next(i))
+            };
+            assert_eq!(tour.next(v), after);
+            assert_eq!(tour.prev(v), before);
+        }
+
+        let mut sequence = tour.sequence();
+        if !forward {
+            sequence.reverse();
+        }
+        let start = order.iter().position(|&v| v == 0).unwrap();
+        let expected: Vec<usize> = (0..=n).map(|k| order[(start + k) % n]).collect();
+        assert_eq!(sequence, expected);
+
+        // Positions along the tour from a
+        for _ in 0..50 {
+            let (i, j, k) = (
+                rng.random_range(0..n),
+                rng.random_range(0..n),
+                rng.random_range(0..n),
+            );
+            let from_i = |x: usize| (x + n - i) % n;
+            let (a, b, c) = (order[i], order[j], order[k]);
+            let expected = from_i(j) <= from_i(k);
+            if forward {
+                assert_eq!(tour.between(a, b, c), expected);
+            } else {
+                assert_eq!(tour.between(c, b, a), expected);
+            }
+        }
+
+        forward
+    }
+
+    #[test]
+    fn flips_match_array_tour() {
+        let mut rng = StdRng::seed_from_u64(0);
+
+        for (n, flips) in [(5, 200), (12, 500), (50, 1000), (300, 1000), (1000, 300)] {
+            let mut order: Vec<usize> = (0..n).collect();
+            order[1..].shuffle(&mut rng);
+            let mut sequence = order.clone();
+            sequence.push(0);
+
+            let mut two_level = TwoLevelTour::new(&sequence, n);
+            let mut array = ArrayTour::new(&sequence, n);
+
+            for _ in 0..flips {
+                let (a, b) = (rng.random_range(0..n), rng.random_range(0..n));
+                // Short paths inside a segment and long ones across most of the tour
+                let b = if rng.random_bool(0.5) {
+                    let i = order.iter().position(|&v| v == a).unwrap();
+                    order[(i + rng.random_range(0..4.min(n))) % n]
+                } else {
+                    b
+                };
+
+                // The path from a to b in the reference direction
+                let two_level_forward = assert_same_cycle(&two_level, &order, &mut rng);
+                let array_forward = assert_same_cycle(&array, &order, &mut rng);
+                if two_level_forward {
+                    two_level.flip(a, b);
+                } else {
+                    two_level.flip(b, a);
+                }
+                if array_forward {
+                    array.flip(a, b);
+                } else {
+                    array.flip(b, a);
+                }
+                flip_reference(&mut order, a, b);
+            }
+
+            assert_same_cycle(&two_level, &order, &mut rng);
+            assert_same_cycle(&array, &order, &mut rng);
+        }
+    }
+
+    // 2-opt moves don't depend on the orientation of the tours
+    #[test]
+    fn moves_match_array_tour() {
+        let mut rng = StdRng::seed_from_u64(1);
+        let n = 400;
+        let mut order: Vec<usize> = (0..n).collect();
+        order[1..].shuffle(&mut rng);
+        let mut sequence = order.clone();
+        sequence.push(0);
+
+        let mut two_level = TwoLevelTour::new(&sequence, n);
+        let mut array = ArrayTour::new(&sequence, n);
+
+        for _ in 0..2000 {
+            // Edges (t1, t2) and (t4, t3) in the same direction of the array tour
+            let t1 = rng.random_range(0..n);
+            let forward = rng.random_bool(0.5);
+            let step = |v: usize| {
+                if forward {
+                    array.next(v)
+                } else {
+                    array.prev(v)
+                }
+            };
+            let t2 = step(t1);
+            let mut t4 = step(t2);
+            for _ in 0..rng.random_range(0..n - 3) {
+                t4 = step(t4);
+            }
+            let t3 = step(t4);
+            if t3 == t1 {
+                continue;
+            }
+
+            two_level.move_2opt(t1, t2, t3, t4);
+            array.move_2opt(t1, t2, t3, t4);
+
+            let mut two_level_sequence = two_level.sequence();
+            if two_level_sequence[1] != array.next(0) {
+                two_level_sequence.reverse();
+            }
+            assert_eq!(two_level_sequence, array.sequence());
+        }
+    }
+}
@@ -1,28 +1,189 @@
+mod exact;
 mod ils;
 mod solution;
 
-use ils::ils;
+use exact::{held_karp, max_dimension};
+use ils::{Alpha, Construction, IlsConfig, Improvement, LocalSearch, SegmentInsertion, ils};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
 use std::env;
+use std::thread;
+use std::time::Duration;
+
+fn usage() {
+    eprintln!("Not enough arguments, you need to pass the path to an instance");
+    eprintln!("Use cargo run /path/to/instance or ./path/to/bin /path/to/instance");
+    eprintln!("Options:");
+    eprintln!(
+        "  --exact     solve instances up to {} nodes to optimality",
+        max_dimension()
+    );
+    eprintln!("  --seed N    seed of the random number generator, random by default");
+    eprintln!("ILS options:");
+    eprintln!("  --construction NAME       grasp (default), nn, greedy, christofides, sfc,");
+    eprintln!("                            farthest or cheapest, sfc needs coordinates");
+    eprintln!("  --alpha A                 GRASP RCL size as a fraction of the candidates, random");
+    eprintln!("                            (default, drawn at every step), a value in [0, 1],");
+    eprintln!("                            set:A,B,... (one value per restart) or");
+    eprintln!("                            reactive:A,B,... (favours values with better results)");
+    eprintln!("  --max-iter N              number of restarts");
+    eprintln!("  --max-iter-ils N          perturbations without improvement before restarting");
+    eprintln!("  --time-limit S            stop after S seconds with the best solution found");
+    eprintln!("  --target VALUE            stop once a solution this good is found");
+    eprintln!(
+        "  --fast                    only try moves towards the nearest neighbours of each node"
+    );
+    eprintln!("  --lk                      Lin-Kernighan style search instead of the RVND");
+    eprintln!(
+        "  --candidates K            neighbours per node in fast mode, LK and 3-opt, default is 10"
+    );
+    eprintln!("  --segment-insertion MAX   or-opt with blocks of up to MAX nodes");
+    eprintln!("  --reverse-segments        also reinsert those blocks reversed");
+    eprintln!("  --three-opt               adds 3-opt between neighbouring nodes to the search");
+    eprintln!("  --first-improvement       apply the first improving move of each neighbourhood,");
+    eprintln!("                            node or chain in a random order");
+    eprintln!(
+        "  --threads N               restarts run in parallel, default is the number of cores"
+    );
+}
+
+fn parse_value<T: std::str::FromStr>(value: Option<String>) -> Option<T> {
+    value.and_then(|value| value.parse().ok())
+}
 
 fn main() {
-    let args: Vec<String> = env::args().collect();
+    let mut args = Vec::new();
+    // Solves small instances to optimality instead of running the ILS
+    let mut exact = false;
+    let mut seed = None;
+    let mut max_iter = None;
+    let mut max_iter_ils = None;
+    let mut time_limit = None;
+    let mut target = None;
+    let mut threads = None;
+    let mut segment_max_size = None;
+    let mut reverse_segments = false;
+    let mut local_search = LocalSearch::Rvnd;
+    let mut three_opt = false;
+    let mut improvement = Improvement::Best;
+    let mut construction = Construction::Grasp;
+    let mut alpha = Alpha::Random;
+    let mut neighbours = 10;
+
+    let mut args_it = env::args();
+    while let Some(arg) = args_it.next() {
+        match arg.as_str() {
+            "--exact" => exact = true,
+            "--seed" => match parse_value(args_it.next()) {
+                Some(value) => seed = Some(value),
+                None => return usage(),
+            },
+            "--max-iter" => match parse_value(args_it.next()) {
+                Some(value) if value > 0 => max_iter = Some(value),
+                _ => return usage(),
+            },
+            "--max-iter-ils" => match parse_value(args_it.next()) {
+                Some(value) => max_iter_ils = Some(value),
+                None => return usage(),
+            },
+            "--time-limit" => match parse_value(args_it.next()) {
+                Some(seconds) if seconds >= 0.0 => {
+                    time_limit = Some(Duration::from_secs_f64(seconds))
+                }
+                _ => return usage(),
+            },
+            "--target" => match parse_value(args_it.next()) {
+                Some(value) => target = Some(value),
+                None => return usage(),
+            },
+            "--fast" => local_search = LocalSearch::Candidates,
+            "--lk" => local_search = LocalSearch::LinKernighan,
+            "--candidates" => match parse_value(args_it.next()) {
+                Some(value) if value > 0 => neighbours = value,
+                _ => return usage(),
+            },
+            "--segment-insertion" => match parse_value(args_it.next()) {
+                Some(value) if value > 0 => segment_max_size = Some(value),
+                _ => return usage(),
+            },
+            "--reverse-segments" => reverse_segments = true,
+            "--three-opt" => three_opt = true,
+            "--first-improvement" => improvement = Improvement::First,
+            "--construction" => {
+                construction = match args_it.next().as_deref() {
+                    Some("grasp") => Construction::Grasp,
+                    Some("nn") => Construction::NearestNeighbour,
+                    Some("greedy") => Construction::Greedy,
+                    Some("christofides") => Construction::Christofides,
+                    Some("sfc") => Construction::SpaceFillingCurve,
+                    Some("farthest") => Construction::FarthestInsertion,
+                    Some("cheapest") => Construction::CheapestInsertion,
+                    _ => return usage(),
+                }
+            }
+            "--alpha" => match parse_value(args_it.next()) {
+                Some(value) => alpha = value,
+                None => return usage(),
+            },
+            "--threads" => match parse_value(args_it.next()) {
+                Some(value) if value > 0 => threads = Some(value),
+                _ => return usage(),
+            },
+            _ => args.push(arg),
+        }
+    }
 
     if args.len() != 2 {
-        eprintln!("Not enough arguments, you need to pass the path to an instance");
-        eprintln!("Use cargo run /path/to/instance or ./path/to/bin /path/to/instance");
-        return;
+        return usage();
     }
 
     let instance = instance_reader::read_data(&args[1]);
+    if construction == Construction::SpaceFillingCurve && instance.coords().is_none() {
+        eprintln!("The space filling curve needs an instance with coordinates");
+        return;
+    }
 
-    let max_iter_ils = if instance.dimension < 150 {
+    let max_iter_ils = max_iter_ils.unwrap_or(if instance.dimension < 150 {
         instance.dimension as u32
     } else {
         (instance.dimension / 2) as u32
+    });
+    let config = IlsConfig {
+        time_limit,
+        target,
+        threads: threads
+            .unwrap_or_else(|| thread::available_parallelism().map_or(1, |threads| threads.get())),
+        local_search,
+        neighbours,
+        segment_insertion: segment_max_size.map(|max_size| SegmentInsertion {
+            max_size,
+            reversed: reverse_segments,
+        }),
+        three_opt,
+        improvement,
+        construction,
+        alpha,
+        ..IlsConfig::new(max_iter.unwrap_or(50), max_iter_ils)
     };
 
     println!("{}", instance.name);
-    let s = ils(50, max_iter_ils, &instance);
+    let s = if exact {
+        let Some(s) = held_karp(&instance) else {
+            eprintln!(
+                "The exact solver only handles instances up to {} nodes",
+                max_dimension()
+            );
+            return;
+        };
+        s
+    } else {
+        // The seed is printed so any run can be repeated
+        let seed = seed.unwrap_or_else(|| rand::rng().random());
+        println!("Seed: {seed}");
+        let mut rng = StdRng::seed_from_u64(seed);
+
+        ils(&config, &instance, &mut rng)
+    };
     println!("Solution: ");
     for v in s.sequence.iter().take(s.sequence.len() - 1) {
         print!("{v} -> ");